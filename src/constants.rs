@@ -374,6 +374,9 @@ pub enum IndexSignatureTag {
     //This  tag  specifies  the  uncompressed  size of the Payload archive, including the cpio headers.
     RPMSIGTAG_PAYLOADSIZE = HEADER_TAGBASE + 7,
 
+    // Replaces RPMSIGTAG_SIZE for a combined size of the Header and Payload sections beyond 4 GiB.
+    RPMSIGTAG_LONGSIZE = 270,
+
     //This  index  contains  the  SHA1  checksum  of  the  entire  Header  Section,
     //including the Header Record, Index Records and Header store.
     RPMSIGTAG_SHA1 = 269,
@@ -431,43 +434,79 @@ pub const RPM_MAGIC: [u8; 4] = [0xed, 0xab, 0xee, 0xdb];
 /// header magic recognition (not the lead!)
 pub const HEADER_MAGIC: [u8; 3] = [0x8e, 0xad, 0xe8];
 
+/// file type bits of a file mode, as in `st_mode` (see `man 7 inode`)
+pub const S_IFMT: u32 = 0o170_000;
+pub const S_IFSOCK: u32 = 0o140_000;
+pub const S_IFLNK: u32 = 0o120_000;
+pub const S_IFREG: u32 = 0o100_000;
+pub const S_IFBLK: u32 = 0o060_000;
+pub const S_IFDIR: u32 = 0o040_000;
+pub const S_IFCHR: u32 = 0o020_000;
+pub const S_IFIFO: u32 = 0o010_000;
+
+/// gzip magic, used to detect compressed payloads without a compressor tag
+pub const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
 pub const RPMSENSE_ANY: u32 = 0;
 pub const RPMSENSE_LESS: u32 = 1 << 1;
 pub const RPMSENSE_GREATER: u32 = 1 << 2;
 pub const RPMSENSE_EQUAL: u32 = 1 << 3;
-
-// there is no use yet for those constants. But they are part of the official package
-// so I will leave them in in case we need them later.
-
-// const RPMSENSE_POSTTRANS: u32 = (1 << 5);
-// const RPMSENSE_PREREQ: u32 = (1 << 6);
-// const RPMSENSE_PRETRANS: u32 = (1 << 7);
-// const RPMSENSE_INTERP: u32 = (1 << 8);
-// const RPMSENSE_SCRIPT_PRE: u32 = (1 << 9);
-// const RPMSENSE_SCRIPT_POST: u32 = (1 << 10);
-// const RPMSENSE_SCRIPT_PREUN: u32 = (1 << 11);
-// const RPMSENSE_SCRIPT_POSTUN: u32 = (1 << 12);
-// const RPMSENSE_SCRIPT_VERIFY: u32 = (1 << 13);
-// const RPMSENSE_FIND_REQUIRES: u32 = (1 << 14);
-// const RPMSENSE_FIND_PROVIDES: u32 = (1 << 15);
-// const RPMSENSE_TRIGGERIN: u32 = (1 << 16);
-// const RPMSENSE_TRIGGERUN: u32 = (1 << 17);
-// const RPMSENSE_TRIGGERPOSTUN: u32 = (1 << 18);
-// const RPMSENSE_MISSINGOK: u32 = (1 << 19);
-
-// // for some weird reason, centos packages have another value for rpm lib sense. We have to observe this.
-// const RPMSENSE_RPMLIB: u32 = (1 << 24); //0o100000012;
-// const RPMSENSE_TRIGGERPREIN: u32 = (1 << 25);
-// const RPMSENSE_KEYRING: u32 = (1 << 26);
-// const RPMSENSE_CONFIG: u32 = (1 << 28);
+// bit 4 is unused
+pub const RPMSENSE_POSTTRANS: u32 = 1 << 5;
+pub const RPMSENSE_PREREQ: u32 = 1 << 6;
+pub const RPMSENSE_PRETRANS: u32 = 1 << 7;
+pub const RPMSENSE_INTERP: u32 = 1 << 8;
+pub const RPMSENSE_SCRIPT_PRE: u32 = 1 << 9;
+pub const RPMSENSE_SCRIPT_POST: u32 = 1 << 10;
+pub const RPMSENSE_SCRIPT_PREUN: u32 = 1 << 11;
+pub const RPMSENSE_SCRIPT_POSTUN: u32 = 1 << 12;
+pub const RPMSENSE_SCRIPT_VERIFY: u32 = 1 << 13;
+pub const RPMSENSE_FIND_REQUIRES: u32 = 1 << 14;
+pub const RPMSENSE_FIND_PROVIDES: u32 = 1 << 15;
+pub const RPMSENSE_TRIGGERIN: u32 = 1 << 16;
+pub const RPMSENSE_TRIGGERUN: u32 = 1 << 17;
+pub const RPMSENSE_TRIGGERPOSTUN: u32 = 1 << 18;
+pub const RPMSENSE_MISSINGOK: u32 = 1 << 19;
+// bits 20 to 23 are unused
+
+// for some weird reason, centos packages have another value for rpm lib sense. We have to observe this.
+// That is because rpmlib() dependencies are always combined with RPMSENSE_LESS | RPMSENSE_EQUAL,
+// which gives 0o100000012.
+pub const RPMSENSE_RPMLIB: u32 = 1 << 24;
+pub const RPMSENSE_TRIGGERPREIN: u32 = 1 << 25;
+pub const RPMSENSE_KEYRING: u32 = 1 << 26;
+// bit 27 is unused
+pub const RPMSENSE_CONFIG: u32 = 1 << 28;
+pub const RPMSENSE_META: u32 = 1 << 29;
 
 pub const RPMFILE_CONFIG: i32 = 1;
 pub const RPMFILE_DOC: i32 = 1 << 1;
-// const RPMFILE_DONOTUSE: i32 = (1 << 2);
-// const RPMFILE_MISSINGOK: i32 = (1 << 3);
-// const RPMFILE_NOREPLACE: i32 = (1 << 4);
-// const RPMFILE_SPECFILE: i32 = (1 << 5);
-// const RPMFILE_GHOST: i32 = (1 << 6);
-// const RPMFILE_LICENSE: i32 = (1 << 7);
-// const RPMFILE_README: i32 = (1 << 8);
-// const RPMFILE_EXCLUDE: i32 = (1 << 9);
+pub const RPMFILE_ICON: i32 = 1 << 2;
+pub const RPMFILE_MISSINGOK: i32 = 1 << 3;
+pub const RPMFILE_NOREPLACE: i32 = 1 << 4;
+pub const RPMFILE_SPECFILE: i32 = 1 << 5;
+pub const RPMFILE_GHOST: i32 = 1 << 6;
+pub const RPMFILE_LICENSE: i32 = 1 << 7;
+pub const RPMFILE_README: i32 = 1 << 8;
+// bits 9 and 10 are unused
+pub const RPMFILE_PUBKEY: i32 = 1 << 11;
+pub const RPMFILE_ARTIFACT: i32 = 1 << 12;
+
+pub const RPMVERIFY_FILEDIGEST: u32 = 1;
+pub const RPMVERIFY_FILESIZE: u32 = 1 << 1;
+pub const RPMVERIFY_LINKTO: u32 = 1 << 2;
+pub const RPMVERIFY_USER: u32 = 1 << 3;
+pub const RPMVERIFY_GROUP: u32 = 1 << 4;
+pub const RPMVERIFY_MTIME: u32 = 1 << 5;
+pub const RPMVERIFY_MODE: u32 = 1 << 6;
+pub const RPMVERIFY_RDEV: u32 = 1 << 7;
+pub const RPMVERIFY_CAPS: u32 = 1 << 8;
+/// default of rpmbuild, includes bits not assigned yet
+pub const RPMVERIFY_ALL: u32 = !0;
+
+pub const RPMSCRIPT_FLAG_EXPAND: u32 = 1;
+pub const RPMSCRIPT_FLAG_QFORMAT: u32 = 1 << 1;
+pub const RPMSCRIPT_FLAG_CRITICAL: u32 = 1 << 2;
+
+/// interpreter of scriptlets run by the lua interpreter built into rpm
+pub const RPMSCRIPT_LUA: &str = "<lua>";
@@ -58,6 +58,41 @@ pub enum RPMError {
     #[error("invalid destination path {path} - {desc}")]
     InvalidDestinationPath { path: String, desc: &'static str },
 
+    #[error("{path} is too large for the payload with a size of {size} bytes")]
+    FileTooLarge { path: String, size: u64 },
+    #[error("device number {major}:{minor} of {path} exceeds the 8 bit major and minor numbers rpm records")]
+    InvalidDeviceNumber {
+        path: String,
+        major: u32,
+        minor: u32,
+    },
+
+    #[error("source file {0} changed while the package was built")]
+    SourceFileChanged(String),
+
+    #[error("invalid glob pattern {pattern}: {source}")]
+    InvalidGlobPattern {
+        pattern: String,
+        source: glob::PatternError,
+    },
+
+    #[error("combined size {0} of header and payload exceeds the reserved RPMSIGTAG_SIZE")]
+    SignatureSizeOverflow(u64),
+
+    #[error(
+        "signature header of {actual} bytes does not match the {reserved} bytes reserved for it"
+    )]
+    SignatureSizeMismatch { reserved: usize, actual: usize },
+
+    #[error("invalid rich dependency: {0}")]
+    InvalidRichDependency(String),
+
+    #[error("invalid scriptlet interpreter {0}, expected an absolute path or <lua>")]
+    InvalidScriptletInterpreter(String),
+
+    #[error("invalid trigger: {0}")]
+    InvalidTrigger(String),
+
     #[error("signature packet not found in what is supposed to be a signature")]
     NoSignatureFound,
     #[error("error creating signature: {0}")]
@@ -77,8 +112,16 @@ pub enum RPMError {
     #[error("unable to find key with key-ref: {key_ref}")]
     KeyNotFoundError { key_ref: String },
 
-    #[error("unknown compressor type {0} - only gzip and none are supported")]
+    #[error("unknown or unsupported compressor type {0}")]
     UnknownCompressorType(String),
+    #[error("unsupported compression level {level} for {compressor}")]
+    UnsupportedCompressionLevel {
+        compressor: &'static str,
+        level: u32,
+    },
+
+    #[error("payload entry {0} has no matching file entry in the header")]
+    PayloadEntryNotInHeader(String),
 }
 
 impl From<nom::Err<(&[u8], nom::error::ErrorKind)>> for RPMError {
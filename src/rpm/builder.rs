@@ -1,7 +1,7 @@
 use sha2::Digest;
-use std::collections::{BTreeMap, BTreeSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
-use std::io::{Read, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 
@@ -32,6 +32,22 @@ fn file_mode(_file: &std::fs::File) -> Result<u32, RPMError> {
     Ok(0)
 }
 
+#[cfg(unix)]
+fn metadata_mode(metadata: &std::fs::Metadata) -> Option<u32> {
+    Some(metadata.permissions().mode())
+}
+
+#[cfg(windows)]
+fn metadata_mode(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
+fn unix_time(time: std::time::SystemTime) -> u32 {
+    time.duration_since(UNIX_EPOCH)
+        .expect("something really wrong with your time")
+        .as_secs() as u32
+}
+
 /// Builder pattern for a full rpm file.
 ///
 /// Prefered method of creating a rpm file.
@@ -43,6 +59,7 @@ pub struct RPMBuilder {
     arch: String,
     uid: Option<u32>,
     gid: Option<u32>,
+    mtime: Option<u32>,
     desc: String,
     release: String,
 
@@ -55,11 +72,19 @@ pub struct RPMBuilder {
     obsoletes: Vec<Dependency>,
     provides: Vec<Dependency>,
     conflicts: Vec<Dependency>,
-
-    pre_inst_script: Option<String>,
-    post_inst_script: Option<String>,
-    pre_uninst_script: Option<String>,
-    post_uninst_script: Option<String>,
+    recommends: Vec<Dependency>,
+    suggests: Vec<Dependency>,
+    supplements: Vec<Dependency>,
+    enhances: Vec<Dependency>,
+
+    pre_inst_script: Option<Scriptlet>,
+    post_inst_script: Option<Scriptlet>,
+    pre_uninst_script: Option<Scriptlet>,
+    post_uninst_script: Option<Scriptlet>,
+    pre_trans_script: Option<Scriptlet>,
+    post_trans_script: Option<Scriptlet>,
+    verify_script: Option<Scriptlet>,
+    triggers: Vec<Trigger>,
 
     changelog_authors: Vec<String>,
     changelog_entries: Vec<String>,
@@ -79,14 +104,23 @@ impl RPMBuilder {
             release: "1".to_string(),
             uid: None,
             gid: None,
+            mtime: None,
             conflicts: Vec::new(),
             provides: Vec::new(),
             obsoletes: Vec::new(),
             requires: Vec::new(),
+            recommends: Vec::new(),
+            suggests: Vec::new(),
+            supplements: Vec::new(),
+            enhances: Vec::new(),
             pre_inst_script: None,
             post_inst_script: None,
             pre_uninst_script: None,
             post_uninst_script: None,
+            pre_trans_script: None,
+            post_trans_script: None,
+            verify_script: None,
+            triggers: Vec::new(),
             files: BTreeMap::new(),
             changelog_authors: Vec::new(),
             changelog_entries: Vec::new(),
@@ -101,6 +135,25 @@ impl RPMBuilder {
         self
     }
 
+    /// Numeric owner of all files in the payload which do not specify one themselves.
+    ///
+    /// Defaults to root.
+    pub fn default_owner(mut self, uid: u32, gid: u32) -> Self {
+        self.uid = Some(uid);
+        self.gid = Some(gid);
+        self
+    }
+
+    /// Modification time in seconds since the epoch of all files which are not read from disk
+    /// and do not specify one themselves.
+    ///
+    /// Defaults to the epoch itself, so that packages are reproducible. Pass the value of
+    /// `SOURCE_DATE_EPOCH` to follow the build environment instead.
+    pub fn default_mtime(mut self, mtime: u32) -> Self {
+        self.mtime = Some(mtime);
+        self
+    }
+
     pub fn compression(mut self, comp: Compressor) -> Self {
         self.compressor = comp;
         self
@@ -117,35 +170,279 @@ impl RPMBuilder {
         self
     }
 
+    /// Add a file from disk.
+    ///
+    /// The file is only read to compute its digest and size, its content is read again
+    /// once the payload is written, so it must not change until the package is built.
+    /// Files of 4 GiB or more are rejected, the newc cpio format of the payload cannot hold them.
     pub fn with_file<T, P>(mut self, source: P, options: T) -> Result<Self, RPMError>
     where
         P: AsRef<Path>,
         T: Into<RPMFileOptions>,
     {
-        let mut input = std::fs::File::open(source)?;
-        let mut content = Vec::new();
-        input.read_to_end(&mut content)?;
+        let source = source.as_ref();
+        let input = std::fs::File::open(source)?;
+        let (sha_checksum, size) = copy_with_digest(&input, std::io::sink())?;
         let mut options = options.into();
         if options.inherit_permissions {
             options.mode = file_mode(&input)? as i32;
         }
+        let modified_at = match options.mtime {
+            Some(mtime) => mtime,
+            None => unix_time(input.metadata()?.modified()?),
+        };
         self.add_data(
-            content,
-            input
-                .metadata()?
-                .modified()?
-                .duration_since(UNIX_EPOCH)
-                .expect("something really wrong with your time")
-                .as_secs() as i32,
+            RPMFileContent::Path(source.to_path_buf()),
+            size,
+            sha_checksum,
+            Some(modified_at),
+            options,
+        )?;
+        Ok(self)
+    }
+
+    /// Add a file which only exists in memory.
+    ///
+    /// The modification time is taken from the options, falling back to
+    /// [`default_mtime`](Self::default_mtime).
+    pub fn with_file_contents<C, T>(mut self, content: C, options: T) -> Result<Self, RPMError>
+    where
+        C: Into<Vec<u8>>,
+        T: Into<RPMFileOptions>,
+    {
+        let content = content.into();
+        let (sha_checksum, size) = copy_with_digest(content.as_slice(), std::io::sink())?;
+        let options = options.into();
+        self.add_data(
+            RPMFileContent::Data(content),
+            size,
+            sha_checksum,
+            options.mtime,
+            options,
+        )?;
+        Ok(self)
+    }
+
+    /// Add all files, directories and symlinks below `source_root` to `dest_prefix`.
+    ///
+    /// Modes and modification times are taken from the source, symlinks are not followed.
+    /// `source_root` itself is not added, use [`with_dir`](Self::with_dir) if the package
+    /// should own it as well.
+    pub fn with_dir_tree<P>(
+        mut self,
+        source_root: P,
+        dest_prefix: &str,
+        options: RPMDirTreeOptions,
+    ) -> Result<Self, RPMError>
+    where
+        P: AsRef<Path>,
+    {
+        let RPMDirTreeOptions {
+            include,
+            exclude,
+            mut file_options,
+        } = options;
+        let compile = |patterns: Vec<String>| {
+            patterns
+                .into_iter()
+                .map(|pattern| {
+                    glob::Pattern::new(&pattern)
+                        .map_err(|source| RPMError::InvalidGlobPattern { pattern, source })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let include = compile(include)?;
+        let exclude = compile(exclude)?;
+        let match_options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        let matches_any = |patterns: &[glob::Pattern], path: &Path| {
+            patterns
+                .iter()
+                .any(|pattern| pattern.matches_path_with(path, match_options))
+        };
+
+        let source_root = source_root.as_ref();
+        let dest_prefix = dest_prefix.trim_end_matches('/');
+        let mut pending = vec![PathBuf::new()];
+        while let Some(dir) = pending.pop() {
+            let mut children = std::fs::read_dir(source_root.join(&dir))?
+                .map(|child| child.map(|child| dir.join(child.file_name())))
+                .collect::<Result<Vec<_>, _>>()?;
+            children.sort();
+            for relative in children {
+                // excluding a directory excludes everything within
+                if matches_any(&exclude, &relative) {
+                    continue;
+                }
+                let source = source_root.join(&relative);
+                let metadata = std::fs::symlink_metadata(&source)?;
+                if metadata.is_dir() {
+                    pending.push(relative.clone());
+                }
+                if !include.is_empty() && !matches_any(&include, &relative) {
+                    continue;
+                }
+
+                let dest = format!("{}/{}", dest_prefix, relative.to_string_lossy());
+                let mut options = RPMFileOptions::new(dest).mtime(unix_time(metadata.modified()?));
+                if let Some(mode) = metadata_mode(&metadata) {
+                    options = options.mode(mode as i32);
+                }
+                if metadata.file_type().is_symlink() {
+                    let target = std::fs::read_link(&source)?;
+                    options = options.symlink(target.to_string_lossy());
+                }
+                let options = file_options(&relative, options);
+                self = if metadata.is_dir() {
+                    self.with_dir(options)?
+                } else if metadata.file_type().is_symlink() {
+                    self.with_symlink(options)?
+                } else if metadata.is_file() {
+                    self.with_file(&source, options)?
+                } else {
+                    log::warn!(
+                        "skipping {}, unsupported file type",
+                        source.to_string_lossy()
+                    );
+                    self
+                };
+            }
+        }
+        Ok(self)
+    }
+
+    /// Add a directory owned by the package.
+    ///
+    /// Other than the parent directories of files, which are only recorded by name,
+    /// it has its own mode, user and group and is removed together with the package.
+    /// Without an explicit mode, `0755` is used.
+    pub fn with_dir<T>(mut self, options: T) -> Result<Self, RPMError>
+    where
+        T: Into<RPMFileOptions>,
+    {
+        let mut options = options.into();
+        options.mode = if options.inherit_permissions {
+            (S_IFDIR | 0o755) as i32
+        } else {
+            (S_IFDIR | (options.mode as u32 & 0o7777)) as i32
+        };
+        let dest = options.destination.trim_end_matches('/');
+        options.destination = dest.to_string();
+        self.add_data(
+            RPMFileContent::Data(Vec::new()),
+            0,
+            String::new(),
+            options.mtime,
+            options,
+        )?;
+        Ok(self)
+    }
+
+    /// Add a device node, named pipe or socket.
+    ///
+    /// Without an explicit mode, `0644` is used. rpm records device numbers as 16 bit
+    /// `dev_t`, so major and minor numbers beyond 255 are rejected.
+    pub fn with_special_file<T>(mut self, file: SpecialFile, options: T) -> Result<Self, RPMError>
+    where
+        T: Into<RPMFileOptions>,
+    {
+        let mut options = options.into();
+        let (major, minor) = file.rdev();
+        if major > 0xff || minor > 0xff {
+            return Err(RPMError::InvalidDeviceNumber {
+                path: options.destination,
+                major,
+                minor,
+            });
+        }
+        let permissions = if options.inherit_permissions {
+            0o644
+        } else {
+            options.mode as u32 & 0o7777
+        };
+        options.mode = (file.file_type() | permissions) as i32;
+        options.rdev = file.rdev();
+        self.add_data(
+            RPMFileContent::Data(Vec::new()),
+            0,
+            String::new(),
+            options.mtime,
+            options,
+        )?;
+        Ok(self)
+    }
+
+    /// Add a symbolic link pointing to the target set with
+    /// [`symlink`](super::RPMFileOptionsBuilder::symlink).
+    ///
+    /// Just like rpmbuild does, the target is recorded as content of the link.
+    pub fn with_symlink<T>(mut self, options: T) -> Result<Self, RPMError>
+    where
+        T: Into<RPMFileOptions>,
+    {
+        let mut options = options.into();
+        if options.symlink.is_empty() {
+            return Err(RPMError::InvalidDestinationPath {
+                path: options.destination,
+                desc: "no symlink target given",
+            });
+        }
+        options.mode = (S_IFLNK | 0o777) as i32;
+        let target = options.symlink.clone().into_bytes();
+        self.add_data(
+            RPMFileContent::Data(target.clone()),
+            target.len() as u64,
+            String::new(),
+            options.mtime,
             options,
         )?;
         Ok(self)
     }
 
+    /// Add a hardlink to a regular file added before.
+    ///
+    /// All links of a file share its content, mode, owner and modification time,
+    /// only the destination and flags are taken from the options.
+    pub fn with_hardlink<T>(mut self, target: &str, options: T) -> Result<Self, RPMError>
+    where
+        T: Into<RPMFileOptions>,
+    {
+        let cpio_target = if target.starts_with('.') {
+            target.to_string()
+        } else {
+            format!(".{}", target)
+        };
+        let linked =
+            self.files
+                .get(&cpio_target)
+                .ok_or_else(|| RPMError::InvalidDestinationPath {
+                    path: target.to_string(),
+                    desc: "hardlink target not found",
+                })?;
+        if linked.mode as u16 as u32 & S_IFMT != S_IFREG {
+            return Err(RPMError::InvalidDestinationPath {
+                path: target.to_string(),
+                desc: "hardlink target is not a regular file",
+            });
+        }
+        // always refer to the file holding the content, so it is found in a single step
+        let content = match &linked.content {
+            RPMFileContent::Hardlink(first) => RPMFileContent::Hardlink(first.clone()),
+            _ => RPMFileContent::Hardlink(cpio_target),
+        };
+        // the metadata shared with the target is copied when the package is built
+        self.add_data(content, 0, String::new(), None, options.into())?;
+        Ok(self)
+    }
+
     fn add_data(
         &mut self,
-        content: Vec<u8>,
-        modified_at: i32,
+        content: RPMFileContent,
+        size: u64,
+        sha_checksum: String,
+        modified_at: Option<u32>,
         options: RPMFileOptions,
     ) -> Result<(), RPMError> {
         let dest = options.destination;
@@ -155,6 +452,10 @@ impl RPMBuilder {
                 desc: "invalid start, expected / or ./",
             });
         }
+        // newc cpio headers record the size as 32 bit value
+        if size > u32::MAX as u64 {
+            return Err(RPMError::FileTooLarge { path: dest, size });
+        }
 
         let pb = PathBuf::from(dest.clone());
 
@@ -167,24 +468,31 @@ impl RPMBuilder {
         let (cpio_path, dir) = if dest.starts_with('.') {
             (
                 dest.to_string(),
-                format!("/{}/", parent.strip_prefix(".").unwrap().to_string_lossy()),
+                format!(
+                    "/{}/",
+                    parent
+                        .strip_prefix(".")
+                        .unwrap()
+                        .to_string_lossy()
+                        .trim_end_matches('/')
+                ),
             )
         } else {
             (
                 format!(".{}", dest),
-                format!("{}/", parent.to_string_lossy()),
+                format!("{}/", parent.to_string_lossy().trim_end_matches('/')),
             )
         };
 
-        let mut hasher = sha2::Sha256::default();
-        hasher.update(&content);
-        let hash_result = hasher.finalize();
-        let sha_checksum = format!("{:x}", hash_result);
         let entry = RPMFileEntry {
             base_name: pb.file_name().unwrap().to_string_lossy().to_string(),
-            size: content.len() as i32,
-            content: Some(content),
+            size,
+            content,
             flag: options.flag,
+            verify_flags: options.verify_flags,
+            rdev: options.rdev,
+            uid: options.uid,
+            gid: options.gid,
             user: options.user,
             group: options.group,
             mode: options.mode as i16,
@@ -194,31 +502,72 @@ impl RPMBuilder {
             sha_checksum,
         };
 
+        // links copy the metadata of their target, which would end up with another file type
+        let links_to_dest = |file: &RPMFileEntry| match &file.content {
+            RPMFileContent::Hardlink(target) => *target == cpio_path,
+            _ => false,
+        };
+        let is_linked =
+            self.files.contains_key(&cpio_path) && self.files.values().any(links_to_dest);
+        if is_linked {
+            return Err(RPMError::InvalidDestinationPath {
+                path: dest,
+                desc: "hardlink target cannot be replaced",
+            });
+        }
+
         self.directories.insert(dir);
         self.files.entry(cpio_path).or_insert(entry);
         Ok(())
     }
 
-    pub fn pre_install_script<T: Into<String>>(mut self, content: T) -> Self {
+    pub fn pre_install_script<T: Into<Scriptlet>>(mut self, content: T) -> Self {
         self.pre_inst_script = Some(content.into());
         self
     }
 
-    pub fn post_install_script<T: Into<String>>(mut self, content: T) -> Self {
+    pub fn post_install_script<T: Into<Scriptlet>>(mut self, content: T) -> Self {
         self.post_inst_script = Some(content.into());
         self
     }
 
-    pub fn pre_uninstall_script<T: Into<String>>(mut self, content: T) -> Self {
+    pub fn pre_uninstall_script<T: Into<Scriptlet>>(mut self, content: T) -> Self {
         self.pre_uninst_script = Some(content.into());
         self
     }
 
-    pub fn post_uninstall_script<T: Into<String>>(mut self, content: T) -> Self {
+    pub fn post_uninstall_script<T: Into<Scriptlet>>(mut self, content: T) -> Self {
         self.post_uninst_script = Some(content.into());
         self
     }
 
+    /// `%pretrans`, run once before the transaction containing the package.
+    ///
+    /// Nothing but rpm itself can be relied on being installed at that point, so the
+    /// scriptlet is usually written in lua.
+    pub fn pre_transaction_script<T: Into<Scriptlet>>(mut self, content: T) -> Self {
+        self.pre_trans_script = Some(content.into());
+        self
+    }
+
+    /// `%posttrans`, run once after the transaction containing the package.
+    pub fn post_transaction_script<T: Into<Scriptlet>>(mut self, content: T) -> Self {
+        self.post_trans_script = Some(content.into());
+        self
+    }
+
+    /// `%verifyscript`, run by `rpm -V` in addition to the checks of the files.
+    pub fn verify_script<T: Into<Scriptlet>>(mut self, content: T) -> Self {
+        self.verify_script = Some(content.into());
+        self
+    }
+
+    /// Run a scriptlet when another package is installed or removed.
+    pub fn trigger(mut self, trigger: Trigger) -> Self {
+        self.triggers.push(trigger);
+        self
+    }
+
     pub fn release(mut self, release: u16) -> Self {
         self.release = format!("{}", release);
         self
@@ -244,11 +593,39 @@ impl RPMBuilder {
         self
     }
 
+    /// Weak dependency installed along with this package by default, but which may be
+    /// removed or left out.
+    pub fn recommends(mut self, dep: Dependency) -> Self {
+        self.recommends.push(dep);
+        self
+    }
+
+    /// Weak dependency merely hinted at, it is not installed by default.
+    pub fn suggests(mut self, dep: Dependency) -> Self {
+        self.suggests.push(dep);
+        self
+    }
+
+    /// Reverse of [`recommends`](Self::recommends), this package is installed by default
+    /// along with the matching packages.
+    pub fn supplements(mut self, dep: Dependency) -> Self {
+        self.supplements.push(dep);
+        self
+    }
+
+    /// Reverse of [`suggests`](Self::suggests), this package is hinted at for the matching
+    /// packages.
+    pub fn enhances(mut self, dep: Dependency) -> Self {
+        self.enhances.push(dep);
+        self
+    }
+
     /// build without a signature
     ///
     /// ignores a present key, if any
     pub fn build(self) -> Result<RPMPackage, RPMError> {
-        let (lead, header_idx_tag, content) = self.prepare_data()?;
+        let (lead, header_idx_tag, payload) = self.prepare_data()?;
+        let content = payload.into_content()?;
 
         let mut header = Vec::with_capacity(128);
         header_idx_tag.write(&mut header)?;
@@ -257,14 +634,14 @@ impl RPMBuilder {
         let (header_digest_sha1, header_and_content_digest_md5) =
             Self::derive_hashes(header.as_slice(), content.as_slice())?;
 
-        let header_and_content_len = header.len() + content.len();
+        let header_and_content_len = (header.len() + content.len()) as u64;
 
         let digest_header = Header::<IndexSignatureTag>::builder()
             .add_digest(
                 header_digest_sha1.as_str(),
                 header_and_content_digest_md5.as_slice(),
             )
-            .build(header_and_content_len as i32);
+            .build(header_and_content_len);
 
         let metadata = RPMPackageMetadata {
             lead,
@@ -275,6 +652,68 @@ impl RPMBuilder {
         Ok(pkg)
     }
 
+    /// build without a signature, streaming the payload into `out`
+    ///
+    /// Other than [`build`](Self::build), the payload is never held in memory as a whole.
+    /// Files are read from disk while being compressed and the compressed data is written
+    /// to `out` right away. The signature header depends on the payload, so space is reserved
+    /// for it and it is filled in once the payload is complete, which is why `out` must be seekable.
+    ///
+    /// ignores a present key, if any
+    pub fn build_to<W: Write + Seek>(self, out: &mut W) -> Result<RPMPackageMetadata, RPMError> {
+        let (lead, header_idx_tag, payload) = self.prepare_data()?;
+
+        let mut header = Vec::with_capacity(128);
+        header_idx_tag.write(&mut header)?;
+        let header = header;
+
+        let header_digest_sha1 = sha1::Sha1::from(&header).digest().to_string();
+        // the width of the size tag has to be chosen before the compressed size is known
+        let long_size = header.len() as u64 + payload.size_bound() > u32::MAX as u64;
+        let placeholder = Header::<IndexSignatureTag>::builder()
+            .add_digest(header_digest_sha1.as_str(), &[0; 16])
+            .build_with_size(0, long_size);
+
+        lead.write(out)?;
+        let signature_position = out.stream_position()?;
+        placeholder.write_signature(out)?;
+        out.write_all(&header)?;
+
+        let (header_and_content_digest_md5, header_and_content_len) =
+            payload.write_to(out, &header)?;
+        let end_position = out.stream_position()?;
+        if !long_size && header_and_content_len > u32::MAX as u64 {
+            return Err(RPMError::SignatureSizeOverflow(header_and_content_len));
+        }
+
+        let digest_header = Header::<IndexSignatureTag>::builder()
+            .add_digest(
+                header_digest_sha1.as_str(),
+                header_and_content_digest_md5.as_slice(),
+            )
+            .build_with_size(header_and_content_len, long_size);
+        let mut signature = Vec::new();
+        digest_header.write_signature(&mut signature)?;
+        let mut reserved = Vec::new();
+        placeholder.write_signature(&mut reserved)?;
+        if signature.len() != reserved.len() {
+            return Err(RPMError::SignatureSizeMismatch {
+                reserved: reserved.len(),
+                actual: signature.len(),
+            });
+        }
+
+        out.seek(SeekFrom::Start(signature_position))?;
+        out.write_all(&signature)?;
+        out.seek(SeekFrom::Start(end_position))?;
+
+        Ok(RPMPackageMetadata {
+            lead,
+            signature: digest_header,
+            header: header_idx_tag,
+        })
+    }
+
     /// use an external signer to sing and build
     ///
     /// See `signature::Signing` for more details.
@@ -283,7 +722,8 @@ impl RPMBuilder {
     where
         S: signature::Signing<crate::signature::algorithm::RSA>,
     {
-        let (lead, header_idx_tag, content) = self.prepare_data()?;
+        let (lead, header_idx_tag, payload) = self.prepare_data()?;
+        let content = payload.into_content()?;
 
         let mut header = Vec::with_capacity(128);
         header_idx_tag.write(&mut header)?;
@@ -292,7 +732,7 @@ impl RPMBuilder {
         let (header_digest_sha1, header_and_content_digest_md5) =
             Self::derive_hashes(header.as_slice(), content.as_slice())?;
 
-        let header_and_content_len = header.len() + content.len();
+        let header_and_content_len = (header.len() + content.len()) as u64;
 
         let builder = Header::<IndexSignatureTag>::builder().add_digest(
             header_digest_sha1.as_str(),
@@ -310,7 +750,7 @@ impl RPMBuilder {
                     rsa_sig_header_only.as_ref(),
                     rsa_sig_header_and_archive.as_ref(),
                 )
-                .build(header_and_content_len as i32)
+                .build(header_and_content_len)
         };
 
         let metadata = RPMPackageMetadata {
@@ -339,17 +779,28 @@ impl RPMBuilder {
         Ok((digest_sha1, digest_md5.to_vec()))
     }
 
-    /// prepapre all rpm headers including content
+    /// prepapre all rpm headers, the payload is written afterwards
     ///
     /// @todo split this into multiple `fn`s, one per `IndexTag`-group.
-    fn prepare_data(mut self) -> Result<(Lead, Header<IndexTag>, Vec<u8>), RPMError> {
+    fn prepare_data(mut self) -> Result<(Lead, Header<IndexTag>, PreparedPayload), RPMError> {
         // signature depends on header and payload. So we build these two first.
         // then the signature. Then we stitch all toghether.
         // Lead is not important. just build it here
 
         let lead = Lead::new(&self.name);
 
-        let mut ino_index = 1;
+        // rpm only evaluates rich dependencies of what a package needs, not of what it offers
+        if let Some(rich) = self
+            .provides
+            .iter()
+            .chain(&self.obsoletes)
+            .find(|dependency| dependency.dep_name.starts_with('('))
+        {
+            return Err(RPMError::InvalidRichDependency(format!(
+                "{} is not allowed in provides or obsoletes",
+                rich.dep_name
+            )));
+        }
 
         let mut file_sizes = Vec::new();
         let mut file_modes = Vec::new();
@@ -362,28 +813,39 @@ impl RPMBuilder {
         let mut file_groupnames = Vec::new();
         let mut file_devices = Vec::new();
         let mut file_inodes = Vec::new();
+        let mut file_uids = Vec::new();
+        let mut file_gids = Vec::new();
         let mut file_langs = Vec::new();
         let mut file_verify_flags = Vec::new();
         let mut dir_indixes = Vec::new();
         let mut base_names = Vec::new();
 
-        let mut combined_file_sizes = 0;
-
-        for (cpio_path, entry) in self.files.iter() {
-            combined_file_sizes += entry.size;
-            file_sizes.push(entry.size);
+        let mut combined_file_sizes: u64 = 0;
+
+        resolve_hardlinks(&mut self.files)?;
+        let inodes = assign_inodes(&self.files);
+        for (entry, inode) in self.files.values().zip(&inodes) {
+            // the content of hardlinks is only installed once, ghosts are not installed at all
+            if inode.with_content && entry.flag & RPMFILE_GHOST == 0 {
+                combined_file_sizes += entry.size;
+            }
+            // `add_data` makes sure the sizes fit, just like the newc cpio format itself
+            file_sizes.push(entry.size as i32);
             file_modes.push(entry.mode);
-            // I really do not know the difference. It seems like file_rdevice is always 0 and file_device number always 1.
-            // Who knows, who cares.
-            file_rdevs.push(0);
+            // the device the file resides on, which is the same for all files
             file_devices.push(1);
-            file_mtimes.push(entry.modified_at);
+            // the device a device node refers to, as 16 bit `dev_t` just like rpm itself
+            let (major, minor) = entry.rdev;
+            file_rdevs.push((((major & 0xff) << 8) | (minor & 0xff)) as u16 as i16);
+            file_mtimes.push(entry.modified_at.or(self.mtime).unwrap_or(0) as i32);
             file_hashes.push(entry.sha_checksum.to_owned());
             file_linktos.push(entry.link.to_owned());
             file_flags.push(entry.flag);
             file_usernames.push(entry.user.to_owned());
+            file_uids.push(entry.uid.or(self.uid).unwrap_or(0) as i32);
+            file_gids.push(entry.gid.or(self.gid).unwrap_or(0) as i32);
             file_groupnames.push(entry.group.to_owned());
-            file_inodes.push(ino_index as i32);
+            file_inodes.push(inode.ino as i32);
             file_langs.push("".to_string());
             let index = self
                 .directories
@@ -392,22 +854,117 @@ impl RPMBuilder {
                 .unwrap();
             dir_indixes.push(index as i32);
             base_names.push(entry.base_name.to_owned());
-            file_verify_flags.push(-1);
-            let content = entry.content.to_owned().unwrap();
-            let mut writer = cpio::newc::Builder::new(&cpio_path)
-                .mode(entry.mode as u32)
-                .ino(ino_index as u32)
-                .uid(self.uid.unwrap_or(0))
-                .gid(self.gid.unwrap_or(0))
-                .write(&mut self.compressor, content.len() as u32);
-
-            writer.write_all(&content)?;
-            writer.finish()?;
+            file_verify_flags.push(entry.verify_flags as i32);
+        }
+
+        // scriptlet interpreters need to be installed before the scriptlets are run
+        let mut interpreters = Vec::new();
+        for (scriptlet, tags) in self.scriptlets() {
+            interpreters.extend(interpreter_requirement(scriptlet, tags.sense)?);
+        }
+        for trigger in &self.triggers {
+            if trigger.conditions.is_empty() {
+                return Err(RPMError::InvalidTrigger("no condition given".to_string()));
+            }
+            if !trigger.scriptlet.arguments.is_empty() {
+                return Err(RPMError::InvalidTrigger(format!(
+                    "interpreter arguments of {} are not supported",
+                    trigger.scriptlet.interpreter
+                )));
+            }
+            interpreters.extend(interpreter_requirement(
+                &trigger.scriptlet,
+                DependencyFlags::empty(),
+            )?);
+        }
+        self.requires.extend(interpreters);
 
-            ino_index += 1;
+        let offset = 0;
+        let mut scriptlet_records = Vec::new();
+        for (scriptlet, tags) in self.scriptlets() {
+            // just like rpmbuild, scriptlets only running the interpreter have no body
+            if !scriptlet.body.is_empty() {
+                scriptlet_records.push(IndexEntry::new(
+                    tags.script,
+                    offset,
+                    IndexData::StringTag(scriptlet.body.clone()),
+                ));
+            }
+            // the interpreter and its arguments, a plain string without arguments
+            let program = if scriptlet.arguments.is_empty() {
+                IndexData::StringTag(scriptlet.interpreter.clone())
+            } else {
+                let mut program = vec![scriptlet.interpreter.clone()];
+                program.extend(scriptlet.arguments.iter().cloned());
+                IndexData::StringArray(program)
+            };
+            scriptlet_records.push(IndexEntry::new(tags.program, offset, program));
+            if !scriptlet.flags.is_empty() {
+                scriptlet_records.push(IndexEntry::new(
+                    tags.flags,
+                    offset,
+                    IndexData::Int32(vec![scriptlet.flags.bits() as i32]),
+                ));
+            }
         }
 
-        self.requires.push(Dependency::any("/bin/sh".to_string()));
+        if !self.triggers.is_empty() {
+            let mut scripts = Vec::new();
+            let mut programs = Vec::new();
+            let mut script_flags = Vec::new();
+            let mut names = Vec::new();
+            let mut versions = Vec::new();
+            let mut flags = Vec::new();
+            let mut indices = Vec::new();
+            for (index, trigger) in self.triggers.iter().enumerate() {
+                scripts.push(trigger.scriptlet.body.clone());
+                programs.push(trigger.scriptlet.interpreter.clone());
+                script_flags.push(trigger.scriptlet.flags.bits() as i32);
+                for condition in &trigger.conditions {
+                    names.push(condition.dep_name.clone());
+                    versions.push(condition.version.clone());
+                    flags.push((condition.sense | trigger.trigger_type.sense()).bits() as i32);
+                    indices.push(index as i32);
+                }
+            }
+            scriptlet_records.push(IndexEntry::new(
+                IndexTag::RPMTAG_TRIGGERSCRIPTS,
+                offset,
+                IndexData::StringArray(scripts),
+            ));
+            scriptlet_records.push(IndexEntry::new(
+                IndexTag::RPMTAG_TRIGGERSCRIPTPROG,
+                offset,
+                IndexData::StringArray(programs),
+            ));
+            if script_flags.iter().any(|flags| *flags != 0) {
+                scriptlet_records.push(IndexEntry::new(
+                    IndexTag::RPMTAG_TRIGGERSCRIPTFLAGS,
+                    offset,
+                    IndexData::Int32(script_flags),
+                ));
+            }
+            scriptlet_records.push(IndexEntry::new(
+                IndexTag::RPMTAG_TRIGGERNAME,
+                offset,
+                IndexData::StringArray(names),
+            ));
+            scriptlet_records.push(IndexEntry::new(
+                IndexTag::RPMTAG_TRIGGERVERSION,
+                offset,
+                IndexData::StringArray(versions),
+            ));
+            scriptlet_records.push(IndexEntry::new(
+                IndexTag::RPMTAG_TRIGGERFLAGS,
+                offset,
+                IndexData::Int32(flags),
+            ));
+            scriptlet_records.push(IndexEntry::new(
+                IndexTag::RPMTAG_TRIGGERINDEX,
+                offset,
+                IndexData::Int32(indices),
+            ));
+        }
 
         self.provides
             .push(Dependency::eq(self.name.clone(), self.version.clone()));
@@ -416,13 +973,18 @@ impl RPMBuilder {
             self.version.clone(),
         ));
 
+        // after the self-provides, which are versioned dependencies as well
+        for (name, version) in self.rpmlib_capabilities() {
+            self.requires.push(Dependency::rpm_lib(name, version));
+        }
+
         let mut provide_names = Vec::new();
         let mut provide_flags = Vec::new();
         let mut provide_versions = Vec::new();
 
         for d in self.provides.into_iter() {
             provide_names.push(d.dep_name);
-            provide_flags.push(d.sense as i32);
+            provide_flags.push(d.sense.bits() as i32);
             provide_versions.push(d.version);
         }
 
@@ -432,7 +994,7 @@ impl RPMBuilder {
 
         for d in self.obsoletes.into_iter() {
             obsolete_names.push(d.dep_name);
-            obsolete_flags.push(d.sense as i32);
+            obsolete_flags.push(d.sense.bits() as i32);
             obsolete_versions.push(d.version);
         }
 
@@ -442,7 +1004,7 @@ impl RPMBuilder {
 
         for d in self.requires.into_iter() {
             require_names.push(d.dep_name);
-            require_flags.push(d.sense as i32);
+            require_flags.push(d.sense.bits() as i32);
             require_versions.push(d.version);
         }
 
@@ -452,11 +1014,24 @@ impl RPMBuilder {
 
         for d in self.conflicts.into_iter() {
             conflicts_names.push(d.dep_name);
-            conflicts_flags.push(d.sense as i32);
+            conflicts_flags.push(d.sense.bits() as i32);
             conflicts_versions.push(d.version);
         }
 
-        let offset = 0;
+        // RPMTAG_SIZE is an unsigned 32 bit value, larger packages need RPMTAG_LONGSIZE instead
+        let size_entry = if combined_file_sizes > u32::MAX as u64 {
+            IndexEntry::new(
+                IndexTag::RPMTAG_LONGSIZE,
+                offset,
+                IndexData::Int64(vec![combined_file_sizes as i64]),
+            )
+        } else {
+            IndexEntry::new(
+                IndexTag::RPMTAG_SIZE,
+                offset,
+                IndexData::Int32(vec![combined_file_sizes as i32]),
+            )
+        };
         let mut actual_records = vec![
             IndexEntry::new(
                 IndexTag::RPMTAG_HEADERI18NTABLE,
@@ -493,11 +1068,7 @@ impl RPMBuilder {
                 offset,
                 IndexData::StringTag(self.desc),
             ),
-            IndexEntry::new(
-                IndexTag::RPMTAG_SIZE,
-                offset,
-                IndexData::Int32(vec![combined_file_sizes]),
-            ),
+            size_entry,
             IndexEntry::new(
                 IndexTag::RPMTAG_LICENSE,
                 offset,
@@ -627,9 +1198,25 @@ impl RPMBuilder {
             ),
         ];
 
-        let possible_compression_details = self.compressor.get_details();
+        // rpm only uses the names, the numeric ids are merely recorded if given explicitly
+        if self
+            .files
+            .values()
+            .any(|entry| entry.uid.is_some() || entry.gid.is_some())
+        {
+            actual_records.push(IndexEntry::new(
+                IndexTag::RPMTAG_FILEUIDS,
+                offset,
+                IndexData::Int32(file_uids),
+            ));
+            actual_records.push(IndexEntry::new(
+                IndexTag::RPMTAG_FILEGIDS,
+                offset,
+                IndexData::Int32(file_gids),
+            ));
+        }
 
-        if let Some(details) = possible_compression_details {
+        if let Some(details) = self.compressor.get_details() {
             actual_records.push(IndexEntry::new(
                 IndexTag::RPMTAG_PAYLOADCOMPRESSOR,
                 offset,
@@ -638,7 +1225,7 @@ impl RPMBuilder {
             actual_records.push(IndexEntry::new(
                 IndexTag::RPMTAG_PAYLOADFLAGS,
                 offset,
-                IndexData::StringTag(details.compression_level.to_string()),
+                IndexData::StringTag(details.compression_level),
             ));
         }
 
@@ -714,69 +1301,542 @@ impl RPMBuilder {
             ));
         }
 
-        if self.pre_inst_script.is_some() {
+        let weak_dependencies = [
+            (
+                self.recommends,
+                IndexTag::RPMTAG_RECOMMENDNAME,
+                IndexTag::RPMTAG_RECOMMENDVERSION,
+                IndexTag::RPMTAG_RECOMMENDFLAGS,
+            ),
+            (
+                self.suggests,
+                IndexTag::RPMTAG_SUGGESTNAME,
+                IndexTag::RPMTAG_SUGGESTVERSION,
+                IndexTag::RPMTAG_SUGGESTFLAGS,
+            ),
+            (
+                self.supplements,
+                IndexTag::RPMTAG_SUPPLEMENTNAME,
+                IndexTag::RPMTAG_SUPPLEMENTVERSION,
+                IndexTag::RPMTAG_SUPPLEMENTFLAGS,
+            ),
+            (
+                self.enhances,
+                IndexTag::RPMTAG_ENHANCENAME,
+                IndexTag::RPMTAG_ENHANCEVERSION,
+                IndexTag::RPMTAG_ENHANCEFLAGS,
+            ),
+        ];
+        for (dependencies, name_tag, version_tag, flags_tag) in weak_dependencies {
+            if dependencies.is_empty() {
+                continue;
+            }
+            let mut names = Vec::new();
+            let mut flags = Vec::new();
+            let mut versions = Vec::new();
+            for d in dependencies.into_iter() {
+                names.push(d.dep_name);
+                flags.push(d.sense.bits() as i32);
+                versions.push(d.version);
+            }
             actual_records.push(IndexEntry::new(
-                IndexTag::RPMTAG_PREIN,
+                name_tag,
                 offset,
-                IndexData::StringTag(self.pre_inst_script.unwrap()),
+                IndexData::StringArray(names),
             ));
-        }
-        if self.post_inst_script.is_some() {
             actual_records.push(IndexEntry::new(
-                IndexTag::RPMTAG_POSTIN,
+                version_tag,
                 offset,
-                IndexData::StringTag(self.post_inst_script.unwrap()),
+                IndexData::StringArray(versions),
             ));
+            actual_records.push(IndexEntry::new(flags_tag, offset, IndexData::Int32(flags)));
         }
 
-        if self.pre_uninst_script.is_some() {
-            actual_records.push(IndexEntry::new(
-                IndexTag::RPMTAG_PREUN,
-                offset,
-                IndexData::StringTag(self.pre_uninst_script.unwrap()),
-            ));
+        actual_records.extend(scriptlet_records);
+
+        let header = Header::from_entries(actual_records, IndexTag::RPMTAG_HEADERIMMUTABLE);
+
+        let payload = PreparedPayload {
+            archive: Archive {
+                files: self.files,
+                inodes,
+                uid: self.uid.unwrap_or(0),
+                gid: self.gid.unwrap_or(0),
+            },
+            compressor: self.compressor,
+        };
+
+        Ok((lead, header, payload))
+    }
+
+    /// The scriptlets present along with their header tags.
+    fn scriptlets(&self) -> Vec<(&Scriptlet, &'static ScriptletTags)> {
+        [
+            (&self.pre_inst_script, &PRE_INSTALL_TAGS),
+            (&self.post_inst_script, &POST_INSTALL_TAGS),
+            (&self.pre_uninst_script, &PRE_UNINSTALL_TAGS),
+            (&self.post_uninst_script, &POST_UNINSTALL_TAGS),
+            (&self.pre_trans_script, &PRE_TRANSACTION_TAGS),
+            (&self.post_trans_script, &POST_TRANSACTION_TAGS),
+            (&self.verify_script, &VERIFY_TAGS),
+        ]
+        .iter()
+        .filter_map(|(scriptlet, tags)| scriptlet.as_ref().map(|scriptlet| (scriptlet, *tags)))
+        .collect()
+    }
+
+    /// Names and versions of the `rpmlib()` capabilities required to install the package,
+    /// derived from the features it uses.
+    ///
+    /// `rpmlib(LargeFiles)` is never needed, the newc cpio format limits single files to 4 GiB.
+    fn rpmlib_capabilities(&self) -> Vec<(&'static str, &'static str)> {
+        let mut capabilities = Vec::new();
+        let dependencies = || {
+            self.requires
+                .iter()
+                .chain(&self.conflicts)
+                .chain(&self.obsoletes)
+                .chain(&self.provides)
+                .chain(&self.recommends)
+                .chain(&self.suggests)
+                .chain(&self.supplements)
+                .chain(&self.enhances)
+        };
+        if dependencies().any(|dependency| {
+            !dependency.version.is_empty() && !dependency.sense.contains(DependencyFlags::RPMLIB)
+        }) {
+            capabilities.push(("rpmlib(VersionedDependencies)", "3.0.3-1"));
+        }
+        if !self.files.is_empty() {
+            // file names are split into RPMTAG_DIRNAMES and RPMTAG_BASENAMES
+            capabilities.push(("rpmlib(CompressedFileNames)", "3.0.4-1"));
+            // file digests are sha256 instead of md5
+            capabilities.push(("rpmlib(FileDigests)", "4.6.0-1"));
+            // cpio paths start with "./"
+            capabilities.push(("rpmlib(PayloadFilesHavePrefix)", "4.0-1"));
         }
+        if self
+            .files
+            .values()
+            .any(|entry| matches!(entry.content, RPMFileContent::Hardlink(_)))
+        {
+            // only the last member of a hardlink set carries the content
+            capabilities.push(("rpmlib(PartialHardlinkSets)", "4.0.4-1"));
+        }
+        if let Some(capability) = self
+            .compressor
+            .get_details()
+            .and_then(|details| details.rpmlib_capability)
+        {
+            capabilities.push(capability);
+        }
+        let scriptlets = self
+            .scriptlets()
+            .into_iter()
+            .map(|(scriptlet, _)| scriptlet)
+            .chain(self.triggers.iter().map(|trigger| &trigger.scriptlet))
+            .collect::<Vec<_>>();
+        if scriptlets
+            .iter()
+            .any(|scriptlet| !scriptlet.arguments.is_empty())
+        {
+            capabilities.push(("rpmlib(ScriptletInterpreterArgs)", "4.0.3-1"));
+        }
+        if scriptlets.iter().any(|scriptlet| scriptlet.is_lua()) {
+            capabilities.push(("rpmlib(BuiltinLuaScripts)", "4.2.2-1"));
+        }
+        if scriptlets
+            .iter()
+            .any(|scriptlet| scriptlet.flags.contains(ScriptletFlags::EXPAND))
+        {
+            capabilities.push(("rpmlib(ScriptletExpansion)", "4.9.0-1"));
+        }
+        let has_rich_dependencies = self
+            .requires
+            .iter()
+            .chain(&self.conflicts)
+            .chain(&self.recommends)
+            .chain(&self.suggests)
+            .chain(&self.supplements)
+            .chain(&self.enhances)
+            .any(|dependency| dependency.dep_name.starts_with('('));
+        if has_rich_dependencies {
+            capabilities.push(("rpmlib(RichDependencies)", "4.12.0-1"));
+        }
+        capabilities
+    }
+}
 
-        if self.post_uninst_script.is_some() {
-            actual_records.push(IndexEntry::new(
-                IndexTag::RPMTAG_POSTUN,
-                offset,
-                IndexData::StringTag(self.post_uninst_script.unwrap()),
-            ));
+/// Requirement of the interpreter of a scriptlet, none for the built-in lua interpreter.
+fn interpreter_requirement(
+    scriptlet: &Scriptlet,
+    sense: DependencyFlags,
+) -> Result<Option<Dependency>, RPMError> {
+    if scriptlet.is_lua() {
+        return Ok(None);
+    }
+    if !scriptlet.interpreter.starts_with('/') {
+        return Err(RPMError::InvalidScriptletInterpreter(
+            scriptlet.interpreter.clone(),
+        ));
+    }
+    Ok(Some(
+        Dependency::any(scriptlet.interpreter.clone()).with_flags(DependencyFlags::INTERP | sense),
+    ))
+}
+
+/// Header tags of a scriptlet, and the context of the requirement of its interpreter.
+struct ScriptletTags {
+    script: IndexTag,
+    program: IndexTag,
+    flags: IndexTag,
+    sense: DependencyFlags,
+}
+
+const PRE_INSTALL_TAGS: ScriptletTags = ScriptletTags {
+    script: IndexTag::RPMTAG_PREIN,
+    program: IndexTag::RPMTAG_PREINPROG,
+    flags: IndexTag::RPMTAG_PREINFLAGS,
+    sense: DependencyFlags::SCRIPT_PRE,
+};
+
+const POST_INSTALL_TAGS: ScriptletTags = ScriptletTags {
+    script: IndexTag::RPMTAG_POSTIN,
+    program: IndexTag::RPMTAG_POSTINPROG,
+    flags: IndexTag::RPMTAG_POSTINFLAGS,
+    sense: DependencyFlags::SCRIPT_POST,
+};
+
+const PRE_UNINSTALL_TAGS: ScriptletTags = ScriptletTags {
+    script: IndexTag::RPMTAG_PREUN,
+    program: IndexTag::RPMTAG_PREUNPROG,
+    flags: IndexTag::RPMTAG_PREUNFLAGS,
+    sense: DependencyFlags::SCRIPT_PREUN,
+};
+
+const POST_UNINSTALL_TAGS: ScriptletTags = ScriptletTags {
+    script: IndexTag::RPMTAG_POSTUN,
+    program: IndexTag::RPMTAG_POSTUNPROG,
+    flags: IndexTag::RPMTAG_POSTUNFLAGS,
+    sense: DependencyFlags::SCRIPT_POSTUN,
+};
+
+const PRE_TRANSACTION_TAGS: ScriptletTags = ScriptletTags {
+    script: IndexTag::RPMTAG_PRETRANS,
+    program: IndexTag::RPMTAG_PRETRANSPROG,
+    flags: IndexTag::RPMTAG_PRETRANSFLAGS,
+    sense: DependencyFlags::PRETRANS,
+};
+
+const POST_TRANSACTION_TAGS: ScriptletTags = ScriptletTags {
+    script: IndexTag::RPMTAG_POSTTRANS,
+    program: IndexTag::RPMTAG_POSTTRANSPROG,
+    flags: IndexTag::RPMTAG_POSTTRANSFLAGS,
+    sense: DependencyFlags::POSTTRANS,
+};
+
+const VERIFY_TAGS: ScriptletTags = ScriptletTags {
+    script: IndexTag::RPMTAG_VERIFYSCRIPT,
+    program: IndexTag::RPMTAG_VERIFYSCRIPTPROG,
+    flags: IndexTag::RPMTAG_VERIFYSCRIPTFLAGS,
+    sense: DependencyFlags::SCRIPT_VERIFY,
+};
+
+/// Selection and options of the files added by [`RPMBuilder::with_dir_tree`].
+///
+/// Glob patterns are matched against paths relative to the source root, `*` does not
+/// match `/`, use `**` to match any number of directories.
+pub struct RPMDirTreeOptions<'a> {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    file_options: FileOptionsFn<'a>,
+}
+
+type FileOptionsFn<'a> = Box<dyn FnMut(&Path, RPMFileOptionsBuilder) -> RPMFileOptionsBuilder + 'a>;
+
+impl<'a> Default for RPMDirTreeOptions<'a> {
+    fn default() -> Self {
+        RPMDirTreeOptions {
+            include: Vec::new(),
+            exclude: Vec::new(),
+            file_options: Box::new(|_, options| options),
         }
+    }
+}
 
-        let header = Header::from_entries(actual_records, IndexTag::RPMTAG_HEADERIMMUTABLE);
+impl<'a> RPMDirTreeOptions<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only add paths matching any of the included patterns.
+    pub fn include<T: Into<String>>(mut self, pattern: T) -> Self {
+        self.include.push(pattern.into());
+        self
+    }
 
-        //those parts seem to break on fedora installations, but it does not seem to matter for centos.
-        // if it turns out that those parts are not really required, we will delete the following comments
-
-        // self.requires.push(Dependency::rpm_lib(
-        //     "rpmlib(VersionedDependencies)".to_string(),
-        //     "3.0.3-1".to_string(),
-        // ));
-
-        // self.requires.push(Dependency::rpm_lib(
-        //     "rpmlib(PayloadFilesHavePrefix)".to_string(),
-        //     "4.0-1".to_string(),
-        // ));
-
-        // self.requires.push(Dependency::rpm_lib(
-        //     "rpmlib(CompressedFileNames)".to_string(),
-        //     "3.0.4-1".to_string(),
-        // ));
-
-        // self.requires.push(Dependency::rpm_lib(
-        //     "rpmlib(PayloadIsXz)".to_string(),
-        //     "5.2-1".to_string(),
-        // ));
-        // self.requires.push(Dependency::rpm_lib(
-        //     "rpmlib(FileDigests)".to_string(),
-        //     "4.6.0-1".to_string(),
-        // ));
-
-        self.compressor = cpio::newc::trailer(self.compressor)?;
-        let content = self.compressor.finish_compression()?;
-
-        Ok((lead, header, content))
+    /// Skip paths matching the pattern, including everything within matching directories.
+    pub fn exclude<T: Into<String>>(mut self, pattern: T) -> Self {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    /// Customize the options of each path, e.g. to mark configuration files.
+    ///
+    /// Called with the path relative to the source root and the options derived from the source.
+    pub fn file_options<F>(mut self, file_options: F) -> Self
+    where
+        F: FnMut(&Path, RPMFileOptionsBuilder) -> RPMFileOptionsBuilder + 'a,
+    {
+        self.file_options = Box::new(file_options);
+        self
+    }
+}
+
+/// Everything required to write the payload once the header is complete.
+struct PreparedPayload {
+    archive: Archive,
+    compressor: Compressor,
+}
+
+impl PreparedPayload {
+    /// compress the whole payload in memory
+    fn into_content(self) -> Result<Vec<u8>, RPMError> {
+        let compressor = self.archive.write(self.compressor)?;
+        compressor.finish_compression()
+    }
+
+    /// Upper bound of the size of the compressed payload.
+    fn size_bound(&self) -> u64 {
+        let archive = self.archive.size_bound();
+        // compression might even grow incompressible content a little
+        archive + archive / 32 + PAYLOAD_CHUNK_SIZE as u64
+    }
+
+    /// compress the payload into `out` chunk by chunk
+    ///
+    /// Returns the md5 digest across `header` and the compressed payload as well as their combined length.
+    fn write_to<W: Write>(self, out: &mut W, header: &[u8]) -> Result<(Vec<u8>, u64), RPMError> {
+        let mut md5 = md5::Md5::default();
+        md5.update(header);
+        let sink = PayloadSink {
+            compressor: self.compressor,
+            out,
+            md5,
+            len: header.len() as u64,
+        };
+        let sink = self.archive.write(sink)?;
+        sink.finish()
+    }
+}
+
+/// The files of the payload along with their inodes, in the same order as they are recorded in the header.
+struct Archive {
+    files: BTreeMap<String, RPMFileEntry>,
+    inodes: Vec<FileInode>,
+    uid: u32,
+    gid: u32,
+}
+
+impl Archive {
+    /// Upper bound of the size of the cpio archive, including headers and padding.
+    fn size_bound(&self) -> u64 {
+        // the newc header has 110 bytes, names and content are padded to 4 bytes
+        let entries: u64 = self
+            .files
+            .iter()
+            .map(|(cpio_path, entry)| 110 + cpio_path.len() as u64 + 4 + entry.size + 3)
+            .sum();
+        // trailer
+        entries + 124
+    }
+
+    /// Write all files as cpio archive.
+    fn write<W: Write>(&self, mut out: W) -> Result<W, RPMError> {
+        for ((cpio_path, entry), inode) in self.files.iter().zip(&self.inodes) {
+            if entry.flag & RPMFILE_GHOST != 0 {
+                continue;
+            }
+            let size = if inode.with_content { entry.size } else { 0 };
+            let mut writer = cpio::newc::Builder::new(cpio_path)
+                .mode(entry.mode as u16 as u32)
+                .ino(inode.ino)
+                .nlink(inode.nlink)
+                .rdev_major(entry.rdev.0)
+                .rdev_minor(entry.rdev.1)
+                .uid(entry.uid.unwrap_or(self.uid))
+                .gid(entry.gid.unwrap_or(self.gid))
+                .write(&mut out, size as u32);
+            if inode.with_content {
+                let content = match &entry.content {
+                    RPMFileContent::Hardlink(target) => &self.files[target].content,
+                    content => content,
+                };
+                match content {
+                    RPMFileContent::Data(data) => writer.write_all(data)?,
+                    RPMFileContent::Path(source) => {
+                        let input = std::fs::File::open(source)?.take(entry.size);
+                        let (sha_checksum, size) = copy_with_digest(input, &mut writer)?;
+                        if size != entry.size || sha_checksum != entry.sha_checksum {
+                            return Err(RPMError::SourceFileChanged(
+                                source.to_string_lossy().to_string(),
+                            ));
+                        }
+                    }
+                    RPMFileContent::Hardlink(_) => {
+                        unreachable!("hardlinks never refer to other hardlinks")
+                    }
+                }
+            }
+            writer.finish()?;
+        }
+        Ok(cpio::newc::trailer(out)?)
+    }
+}
+
+/// Copy the metadata shared with the hardlink targets to their links.
+fn resolve_hardlinks(files: &mut BTreeMap<String, RPMFileEntry>) -> Result<(), RPMError> {
+    let links = files
+        .iter()
+        .filter_map(|(cpio_path, entry)| match &entry.content {
+            RPMFileContent::Hardlink(target) => Some((cpio_path.clone(), target.clone())),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    for (cpio_path, target) in links {
+        let linked = match files.get(&target) {
+            Some(linked) if linked.mode as u16 as u32 & S_IFMT == S_IFREG => linked,
+            _ => {
+                return Err(RPMError::InvalidDestinationPath {
+                    path: target.trim_start_matches('.').to_string(),
+                    desc: "hardlink target is not a regular file",
+                })
+            }
+        };
+        let (size, sha_checksum, modified_at, mode) = (
+            linked.size,
+            linked.sha_checksum.clone(),
+            linked.modified_at,
+            linked.mode,
+        );
+        let (user, group, uid, gid) = (
+            linked.user.clone(),
+            linked.group.clone(),
+            linked.uid,
+            linked.gid,
+        );
+        let entry = files.get_mut(&cpio_path).unwrap();
+        entry.size = size;
+        entry.sha_checksum = sha_checksum;
+        entry.modified_at = modified_at;
+        entry.mode = mode;
+        entry.user = user;
+        entry.group = group;
+        entry.uid = uid;
+        entry.gid = gid;
+    }
+    Ok(())
+}
+
+/// Inode of a file within the archive.
+struct FileInode {
+    ino: u32,
+    /// number of files sharing the inode
+    nlink: u32,
+    /// only the last member of a hardlink set carries the content
+    with_content: bool,
+}
+
+/// Assign the inodes in archive order, sharing them among the members of each hardlink set.
+fn assign_inodes(files: &BTreeMap<String, RPMFileEntry>) -> Vec<FileInode> {
+    let set_of = |(cpio_path, entry): (&String, &RPMFileEntry)| match &entry.content {
+        RPMFileContent::Hardlink(target) => target.clone(),
+        _ => cpio_path.clone(),
+    };
+    // inode, number of members and index of the last member of each set
+    let mut sets = HashMap::<String, (u32, u32, usize)>::new();
+    for (index, file) in files.iter().enumerate() {
+        let ino = sets.len() as u32 + 1;
+        let set = sets.entry(set_of(file)).or_insert((ino, 0, index));
+        set.1 += 1;
+        // ghosts are not part of the archive
+        if file.1.flag & RPMFILE_GHOST == 0 {
+            set.2 = index;
+        }
+    }
+    files
+        .iter()
+        .enumerate()
+        .map(|(index, file)| {
+            let (ino, nlink, last) = sets[&set_of(file)];
+            FileInode {
+                ino,
+                nlink,
+                with_content: index == last,
+            }
+        })
+        .collect()
+}
+
+/// Copy everything from `input` to `out`, returning the hex encoded sha256 digest and the size of the data.
+fn copy_with_digest<R: Read, W: Write>(
+    mut input: R,
+    mut out: W,
+) -> Result<(String, u64), RPMError> {
+    let mut hasher = sha2::Sha256::default();
+    let mut size = 0;
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        hasher.update(&buf[..n]);
+        out.write_all(&buf[..n])?;
+        size += n as u64;
+    }
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
+
+/// Compressed data is moved to `out` once this much has accumulated.
+const PAYLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Moves the compressed payload into `out` while it is produced, keeping track of
+/// the digest and length required for the signature.
+struct PayloadSink<'a, W: Write> {
+    compressor: Compressor,
+    out: &'a mut W,
+    md5: md5::Md5,
+    len: u64,
+}
+
+impl<'a, W: Write> PayloadSink<'a, W> {
+    fn finish(mut self) -> Result<(Vec<u8>, u64), RPMError> {
+        let rest = self.compressor.finish_compression()?;
+        self.md5.update(&rest);
+        self.len += rest.len() as u64;
+        self.out.write_all(&rest)?;
+        Ok((self.md5.finalize().to_vec(), self.len))
+    }
+}
+
+impl<'a, W: Write> Write for PayloadSink<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.compressor.write(buf)?;
+        let compressed = self.compressor.compressed_mut();
+        if compressed.len() >= PAYLOAD_CHUNK_SIZE {
+            self.md5.update(&compressed);
+            self.len += compressed.len() as u64;
+            self.out.write_all(compressed)?;
+            compressed.clear();
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        // see `Compressor::flush`
+        Ok(())
     }
 }
@@ -1,24 +1,58 @@
-use std::io::Write;
+use std::io::{Read, Write};
 
 use crate::errors::*;
 
+/// Default level of gzip compression if created from a string.
+pub const DEFAULT_GZIP_LEVEL: u32 = 9;
+
+/// Default preset of xz and lzma compression if created from a string.
+#[cfg(feature = "compression-xz")]
+pub const DEFAULT_XZ_LEVEL: u32 = 6;
+
+/// Default level of zstd compression if created from a string, as used by Fedora.
+#[cfg(feature = "compression-zstd")]
+pub const DEFAULT_ZSTD_LEVEL: u32 = 19;
+
+/// Default block size of bzip2 compression if created from a string.
+#[cfg(feature = "compression-bzip2")]
+pub const DEFAULT_BZIP2_LEVEL: u32 = 9;
+
 pub enum Compressor {
     None(Vec<u8>),
-    Gzip(libflate::gzip::Encoder<Vec<u8>>),
+    /// gzip encoder with its level
+    Gzip(flate2::write::GzEncoder<Vec<u8>>, u32),
+    /// xz encoder with its preset level
+    #[cfg(feature = "compression-xz")]
+    Xz(xz2::write::XzEncoder<Vec<u8>>, u32),
+    /// legacy lzma encoder with its preset level
+    #[cfg(feature = "compression-xz")]
+    Lzma(xz2::write::XzEncoder<Vec<u8>>, u32),
+    /// zstd encoder with its level
+    #[cfg(feature = "compression-zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, Vec<u8>>, u32),
+    /// bzip2 encoder with its block size level
+    #[cfg(feature = "compression-bzip2")]
+    Bzip2(bzip2::write::BzEncoder<Vec<u8>>, u32),
 }
 
 impl Write for Compressor {
     fn write(&mut self, content: &[u8]) -> Result<usize, std::io::Error> {
         match self {
             Compressor::None(data) => data.write(content),
-            Compressor::Gzip(encoder) => encoder.write(content),
+            Compressor::Gzip(encoder, _) => encoder.write(content),
+            #[cfg(feature = "compression-xz")]
+            Compressor::Xz(encoder, _) | Compressor::Lzma(encoder, _) => encoder.write(content),
+            #[cfg(feature = "compression-zstd")]
+            Compressor::Zstd(encoder, _) => encoder.write(content),
+            #[cfg(feature = "compression-bzip2")]
+            Compressor::Bzip2(encoder, _) => encoder.write(content),
         }
     }
     fn flush(&mut self) -> Result<(), std::io::Error> {
-        match self {
-            Compressor::None(data) => data.flush(),
-            Compressor::Gzip(encoder) => encoder.flush(),
-        }
+        // the compressed data is only accessible after finishing the compression, so there is
+        // nothing to gain from flushing. Flushing the encoders would also degrade compression,
+        // since cpio flushes after each entry, and is not supported by all of them.
+        Ok(())
     }
 }
 
@@ -27,32 +61,231 @@ impl std::str::FromStr for Compressor {
     fn from_str(raw: &str) -> Result<Self, Self::Err> {
         match raw {
             "none" => Ok(Compressor::None(Vec::new())),
-            "gzip" => Ok(Compressor::Gzip(libflate::gzip::Encoder::new(Vec::new())?)),
+            "gzip" => Compressor::gzip(DEFAULT_GZIP_LEVEL),
+            #[cfg(feature = "compression-xz")]
+            "xz" => Compressor::xz(DEFAULT_XZ_LEVEL, 0),
+            #[cfg(feature = "compression-xz")]
+            "lzma" => Compressor::lzma(DEFAULT_XZ_LEVEL),
+            #[cfg(feature = "compression-zstd")]
+            "zstd" => Compressor::zstd(DEFAULT_ZSTD_LEVEL, 0),
+            #[cfg(feature = "compression-bzip2")]
+            "bzip2" => Compressor::bzip2(DEFAULT_BZIP2_LEVEL),
             _ => Err(RPMError::UnknownCompressorType(raw.to_string())),
         }
     }
 }
 
 impl Compressor {
+    /// gzip compression with the given level from 0 to 9.
+    pub fn gzip(level: u32) -> Result<Self, RPMError> {
+        check_level("gzip", level, 0..=9)?;
+        Ok(Compressor::Gzip(
+            flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(level)),
+            level,
+        ))
+    }
+
+    /// xz compression with the given preset level from 0 to 9.
+    ///
+    /// With `workers` greater than 0, the payload is split into blocks which are
+    /// compressed by that many threads.
+    #[cfg(feature = "compression-xz")]
+    pub fn xz(level: u32, workers: u32) -> Result<Self, RPMError> {
+        check_level("xz", level, 0..=9)?;
+        let encoder = if workers > 0 {
+            let stream = xz2::stream::MtStreamBuilder::new()
+                .threads(workers)
+                .preset(level)
+                .check(xz2::stream::Check::Crc64)
+                .encoder()
+                .map_err(std::io::Error::from)?;
+            xz2::write::XzEncoder::new_stream(Vec::new(), stream)
+        } else {
+            xz2::write::XzEncoder::new(Vec::new(), level)
+        };
+        Ok(Compressor::Xz(encoder, level))
+    }
+
+    /// lzma (lzma-alone format) compression with the given preset level from 0 to 9.
+    #[cfg(feature = "compression-xz")]
+    pub fn lzma(level: u32) -> Result<Self, RPMError> {
+        check_level("lzma", level, 0..=9)?;
+        let options = xz2::stream::LzmaOptions::new_preset(level).map_err(std::io::Error::from)?;
+        let stream =
+            xz2::stream::Stream::new_lzma_encoder(&options).map_err(std::io::Error::from)?;
+        Ok(Compressor::Lzma(
+            xz2::write::XzEncoder::new_stream(Vec::new(), stream),
+            level,
+        ))
+    }
+
+    /// zstd compression with the given level from 1 to 19.
+    ///
+    /// With `workers` greater than 0, compression happens in that many background threads.
+    #[cfg(feature = "compression-zstd")]
+    pub fn zstd(level: u32, workers: u32) -> Result<Self, RPMError> {
+        check_level("zstd", level, 1..=19)?;
+        let mut encoder = zstd::stream::write::Encoder::new(Vec::new(), level as i32)?;
+        if workers > 0 {
+            encoder.multithread(workers)?;
+        }
+        Ok(Compressor::Zstd(encoder, level))
+    }
+
+    /// bzip2 compression with the given block size level from 1 to 9.
+    #[cfg(feature = "compression-bzip2")]
+    pub fn bzip2(level: u32) -> Result<Self, RPMError> {
+        check_level("bzip2", level, 1..=9)?;
+        Ok(Compressor::Bzip2(
+            bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::new(level)),
+            level,
+        ))
+    }
+
+    /// Data compressed so far.
+    ///
+    /// All encoders only ever append to it, so it can be drained before the
+    /// compression is finished to keep the memory usage bounded.
+    pub(crate) fn compressed_mut(&mut self) -> &mut Vec<u8> {
+        match self {
+            Compressor::None(data) => data,
+            Compressor::Gzip(encoder, _) => encoder.get_mut(),
+            #[cfg(feature = "compression-xz")]
+            Compressor::Xz(encoder, _) | Compressor::Lzma(encoder, _) => encoder.get_mut(),
+            #[cfg(feature = "compression-zstd")]
+            Compressor::Zstd(encoder, _) => encoder.get_mut(),
+            #[cfg(feature = "compression-bzip2")]
+            Compressor::Bzip2(encoder, _) => encoder.get_mut(),
+        }
+    }
+
     pub(crate) fn finish_compression(self) -> Result<Vec<u8>, RPMError> {
         match self {
             Compressor::None(data) => Ok(data),
-            Compressor::Gzip(encoder) => Ok(encoder.finish().into_result()?),
+            Compressor::Gzip(encoder, _) => Ok(encoder.finish()?),
+            #[cfg(feature = "compression-xz")]
+            Compressor::Xz(encoder, _) | Compressor::Lzma(encoder, _) => Ok(encoder.finish()?),
+            #[cfg(feature = "compression-zstd")]
+            Compressor::Zstd(encoder, _) => Ok(encoder.finish()?),
+            #[cfg(feature = "compression-bzip2")]
+            Compressor::Bzip2(encoder, _) => Ok(encoder.finish()?),
         }
     }
 
     pub(crate) fn get_details(&self) -> Option<CompressionDetails> {
         match self {
             Compressor::None(_) => None,
-            Compressor::Gzip(_) => Some(CompressionDetails {
-                compression_level: "9",
+            Compressor::Gzip(_, level) => Some(CompressionDetails {
+                compression_level: level.to_string(),
                 compression_name: "gzip",
+                rpmlib_capability: None,
+            }),
+            #[cfg(feature = "compression-xz")]
+            Compressor::Xz(_, level) => Some(CompressionDetails {
+                compression_level: level.to_string(),
+                compression_name: "xz",
+                rpmlib_capability: Some(("rpmlib(PayloadIsXz)", "5.2-1")),
+            }),
+            #[cfg(feature = "compression-xz")]
+            Compressor::Lzma(_, level) => Some(CompressionDetails {
+                compression_level: level.to_string(),
+                compression_name: "lzma",
+                rpmlib_capability: Some(("rpmlib(PayloadIsLzma)", "4.4.6-1")),
+            }),
+            #[cfg(feature = "compression-zstd")]
+            Compressor::Zstd(_, level) => Some(CompressionDetails {
+                compression_level: level.to_string(),
+                compression_name: "zstd",
+                rpmlib_capability: Some(("rpmlib(PayloadIsZstd)", "5.4.18-1")),
+            }),
+            #[cfg(feature = "compression-bzip2")]
+            Compressor::Bzip2(_, level) => Some(CompressionDetails {
+                compression_level: level.to_string(),
+                compression_name: "bzip2",
+                rpmlib_capability: Some(("rpmlib(PayloadIsBzip2)", "3.0.5-1")),
             }),
         }
     }
 }
 
+fn check_level(
+    compressor: &'static str,
+    level: u32,
+    supported: std::ops::RangeInclusive<u32>,
+) -> Result<(), RPMError> {
+    if !supported.contains(&level) {
+        return Err(RPMError::UnsupportedCompressionLevel { compressor, level });
+    }
+    Ok(())
+}
+
 pub(crate) struct CompressionDetails {
-    pub(crate) compression_level: &'static str,
+    pub(crate) compression_level: String,
     pub(crate) compression_name: &'static str,
+    /// name and version of the `rpmlib()` capability required to unpack the payload
+    pub(crate) rpmlib_capability: Option<(&'static str, &'static str)>,
+}
+
+/// Decoder for the payload of a parsed package.
+///
+/// The counterpart of [`Compressor`](self::Compressor), selected by the value of
+/// `RPMTAG_PAYLOADCOMPRESSOR`.
+pub enum Decompressor<R: Read> {
+    None(R),
+    Gzip(flate2::read::MultiGzDecoder<R>),
+    /// xz as well as legacy lzma
+    #[cfg(feature = "compression-xz")]
+    Xz(xz2::read::XzDecoder<R>),
+    #[cfg(feature = "compression-zstd")]
+    Zstd(zstd::stream::read::Decoder<'static, std::io::BufReader<R>>),
+    #[cfg(feature = "compression-bzip2")]
+    Bzip2(bzip2::read::MultiBzDecoder<R>),
+}
+
+impl<R: Read> Decompressor<R> {
+    /// Create a decoder for the given compressor name wrapping `reader`.
+    pub fn new(compressor_name: &str, reader: R) -> Result<Self, RPMError> {
+        match compressor_name {
+            "none" => Ok(Decompressor::None(reader)),
+            "gzip" => Ok(Decompressor::Gzip(flate2::read::MultiGzDecoder::new(
+                reader,
+            ))),
+            #[cfg(feature = "compression-xz")]
+            "xz" => Ok(Decompressor::Xz(xz2::read::XzDecoder::new_multi_decoder(
+                reader,
+            ))),
+            #[cfg(feature = "compression-xz")]
+            "lzma" => {
+                let stream = xz2::stream::Stream::new_lzma_decoder(u64::MAX)
+                    .map_err(std::io::Error::from)?;
+                Ok(Decompressor::Xz(xz2::read::XzDecoder::new_stream(
+                    reader, stream,
+                )))
+            }
+            #[cfg(feature = "compression-zstd")]
+            "zstd" => Ok(Decompressor::Zstd(zstd::stream::read::Decoder::new(
+                reader,
+            )?)),
+            #[cfg(feature = "compression-bzip2")]
+            "bzip2" => Ok(Decompressor::Bzip2(bzip2::read::MultiBzDecoder::new(
+                reader,
+            ))),
+            _ => Err(RPMError::UnknownCompressorType(compressor_name.to_string())),
+        }
+    }
+}
+
+impl<R: Read> Read for Decompressor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, std::io::Error> {
+        match self {
+            Decompressor::None(reader) => reader.read(buf),
+            Decompressor::Gzip(decoder) => decoder.read(buf),
+            #[cfg(feature = "compression-xz")]
+            Decompressor::Xz(decoder) => decoder.read(buf),
+            #[cfg(feature = "compression-zstd")]
+            Decompressor::Zstd(decoder) => decoder.read(buf),
+            #[cfg(feature = "compression-bzip2")]
+            Decompressor::Bzip2(decoder) => decoder.read(buf),
+        }
+    }
 }
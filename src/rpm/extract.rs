@@ -0,0 +1,327 @@
+//! Unpacking the payload of a parsed rpm file into a directory.
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+use super::headers::{FileEntry, FileFlags};
+use super::payload::{RPMFileIterator, RPMFileReader};
+use crate::constants::*;
+use crate::errors::*;
+
+/// Options for [`RPMPackage::extract_to`](super::RPMPackage::extract_to).
+#[derive(Default)]
+pub struct RPMExtractOptions {
+    pub(crate) chown: bool,
+}
+
+impl RPMExtractOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply the file owners recorded in the header.
+    ///
+    /// User and group names are resolved against `etc/passwd` and `etc/group` within the target root,
+    /// unknown names fall back to root just like rpm does. Usually requires elevated privileges.
+    pub fn chown(mut self, chown: bool) -> Self {
+        self.chown = chown;
+        self
+    }
+}
+
+pub(crate) fn extract_files<R: Read>(
+    mut files: RPMFileIterator<R>,
+    root: &Path,
+    options: RPMExtractOptions,
+) -> Result<Vec<PathBuf>, RPMError> {
+    fs::create_dir_all(root)?;
+    let mut owners = Owners::new(root);
+    let mut existing_configs = Vec::new();
+    // directory metadata is applied last, otherwise populating them would change their mtime
+    // and restrictive modes might prevent populating them at all
+    let mut directories = Vec::new();
+    let mut extracted_directories = HashSet::new();
+    // members of a hardlink set only carry the content with the last member within the archive
+    let mut pending_links = HashMap::<u32, Vec<FileEntry>>::new();
+
+    while let Some(file) = files.next_file()? {
+        let RPMFileReader { metadata, content } = file;
+        let target = target_path(root, &metadata.path, false)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let existing = fs::symlink_metadata(&target).ok();
+        let file_type = metadata.mode & S_IFMT;
+
+        if file_type == S_IFDIR {
+            // symlinks to directories are kept, like `/lib -> usr/lib` of merged `/usr` layouts
+            let resolved = target_path(root, &metadata.path, true)?;
+            let target = if resolved.is_dir() {
+                resolved
+            } else {
+                if existing.is_some() {
+                    fs::remove_file(&target)?;
+                }
+                fs::create_dir(&target)?;
+                target
+            };
+            extracted_directories.insert(target);
+            directories.push(metadata);
+            continue;
+        }
+
+        if existing.is_some() && metadata.flags.contains(FileFlags::CONFIG) {
+            existing_configs.push(metadata.path);
+            continue;
+        }
+        if let Some(existing) = existing {
+            if existing.is_dir() {
+                // the deferred metadata of the directory would be applied through the symlink
+                if file_type == S_IFLNK && extracted_directories.contains(&target) {
+                    return Err(RPMError::InvalidDestinationPath {
+                        path: metadata.path.to_string_lossy().to_string(),
+                        desc: "symlink replaces an extracted directory",
+                    });
+                }
+                fs::remove_dir_all(&target)?;
+            } else {
+                fs::remove_file(&target)?;
+            }
+        }
+
+        match file_type {
+            S_IFLNK => {
+                let link = if metadata.link.is_empty() {
+                    let mut link = Vec::new();
+                    content.read_to_end(&mut link)?;
+                    String::from_utf8_lossy(&link).to_string()
+                } else {
+                    metadata.link.clone()
+                };
+                symlink(&link, &target)?;
+            }
+            S_IFREG => {
+                if content.entry().file_size() as u64 != metadata.size && metadata.size > 0 {
+                    pending_links
+                        .entry(metadata.inode)
+                        .or_default()
+                        .push(metadata);
+                    continue;
+                }
+                io::copy(content, &mut fs::File::create(&target)?)?;
+                for link_metadata in pending_links.remove(&metadata.inode).unwrap_or_default() {
+                    // parents might have been replaced by symlinks in the meantime
+                    let link = target_path(root, &link_metadata.path, false)?;
+                    if let Some(parent) = link.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::hard_link(&target, &link)?;
+                    apply_metadata(&link, &link_metadata, &options, &mut owners)?;
+                }
+            }
+            _ => {
+                log::warn!(
+                    "skipping {}, unsupported file type {:o}",
+                    metadata.path.display(),
+                    file_type
+                );
+                continue;
+            }
+        }
+        apply_metadata(&target, &metadata, &options, &mut owners)?;
+    }
+
+    for metadata in pending_links.values().flatten() {
+        log::warn!(
+            "skipping {}, no content found for its hardlink set",
+            metadata.path.display()
+        );
+    }
+
+    for metadata in directories.iter().rev() {
+        // parents might have been replaced by symlinks in the meantime
+        let target = target_path(root, &metadata.path, true)?;
+        let is_dir = fs::symlink_metadata(&target)
+            .map(|m| m.is_dir())
+            .unwrap_or(false);
+        if !is_dir {
+            return Err(RPMError::InvalidDestinationPath {
+                path: metadata.path.to_string_lossy().to_string(),
+                desc: "extracted directory was replaced",
+            });
+        }
+        apply_metadata(&target, metadata, &options, &mut owners)?;
+    }
+
+    Ok(existing_configs)
+}
+
+/// Resolve `path` within `root`, following symlinks as if `root` was the file system root.
+///
+/// The last component is only followed with `follow_last`. Fails if the resolved path leaves `root`.
+fn target_path(root: &Path, path: &Path, follow_last: bool) -> Result<PathBuf, RPMError> {
+    let invalid = |desc| RPMError::InvalidDestinationPath {
+        path: path.to_string_lossy().to_string(),
+        desc,
+    };
+    // components still to be resolved in reverse order, symlinks are expanded in place
+    let mut pending = Vec::new();
+    push_components(&mut pending, path).map_err(invalid)?;
+    let mut resolved = PathBuf::new();
+    let mut followed = 0;
+    while let Some(name) = pending.pop() {
+        if name == ".." {
+            if !resolved.pop() {
+                return Err(invalid("path escapes the extraction root"));
+            }
+            continue;
+        }
+        let candidate = root.join(&resolved).join(&name);
+        let is_symlink = fs::symlink_metadata(&candidate)
+            .map(|m| m.file_type().is_symlink())
+            .unwrap_or(false);
+        if !is_symlink || (pending.is_empty() && !follow_last) {
+            resolved.push(name);
+            continue;
+        }
+        followed += 1;
+        if followed > MAX_SYMLINKS {
+            return Err(invalid("too many levels of symlinks"));
+        }
+        let link = fs::read_link(&candidate)?;
+        // absolute targets refer to the root of the extracted tree
+        if link.has_root() {
+            resolved = PathBuf::new();
+        }
+        push_components(&mut pending, &link).map_err(invalid)?;
+    }
+    if resolved.as_os_str().is_empty() {
+        return Err(invalid("path is the extraction root"));
+    }
+    Ok(root.join(resolved))
+}
+
+/// Maximum number of symlinks followed while resolving a single path, just like Linux.
+const MAX_SYMLINKS: usize = 40;
+
+/// Push the components of `path` to `pending` in reverse order, keeping `..` as is.
+fn push_components(pending: &mut Vec<OsString>, path: &Path) -> Result<(), &'static str> {
+    for component in path.components().rev() {
+        match component {
+            Component::RootDir | Component::CurDir => {}
+            Component::Normal(name) => pending.push(name.to_os_string()),
+            Component::ParentDir => pending.push(OsString::from("..")),
+            Component::Prefix(_) => return Err("path has a prefix"),
+        }
+    }
+    Ok(())
+}
+
+fn apply_metadata(
+    target: &Path,
+    metadata: &FileEntry,
+    options: &RPMExtractOptions,
+    owners: &mut Owners,
+) -> Result<(), RPMError> {
+    if options.chown {
+        let uid = owners.uid(&metadata.user);
+        let gid = owners.gid(&metadata.group);
+        chown(target, uid, gid)?;
+    }
+    // permissions of symlinks are meaningless
+    if metadata.mode & S_IFMT != S_IFLNK {
+        set_mode(target, metadata.mode)?;
+    }
+    let mtime = filetime::FileTime::from_unix_time(metadata.modified_at as i64, 0);
+    filetime::set_symlink_file_times(target, mtime, mtime)?;
+    Ok(())
+}
+
+/// Lookup of user and group ids by name within the extraction root.
+struct Owners {
+    users: HashMap<String, u32>,
+    groups: HashMap<String, u32>,
+}
+
+impl Owners {
+    fn new(root: &Path) -> Self {
+        Owners {
+            users: read_id_database(&root.join("etc/passwd")),
+            groups: read_id_database(&root.join("etc/group")),
+        }
+    }
+
+    fn uid(&mut self, user: &str) -> u32 {
+        Self::lookup(&mut self.users, user)
+    }
+
+    fn gid(&mut self, group: &str) -> u32 {
+        Self::lookup(&mut self.groups, group)
+    }
+
+    fn lookup(db: &mut HashMap<String, u32>, name: &str) -> u32 {
+        if let Some(id) = db.get(name) {
+            return *id;
+        }
+        if name != "root" {
+            log::warn!("unknown owner {}, using root instead", name);
+        }
+        // only warn once
+        db.insert(name.to_string(), 0);
+        0
+    }
+}
+
+/// Parse a `name:password:id:...` database such as `/etc/passwd`.
+fn read_id_database(path: &Path) -> HashMap<String, u32> {
+    let raw = match fs::read_to_string(path) {
+        Ok(raw) => raw,
+        Err(_) => return HashMap::new(),
+    };
+    raw.lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let id = fields.nth(1)?.parse().ok()?;
+            Some((name.to_string(), id))
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+fn set_mode(target: &Path, mode: u32) -> Result<(), RPMError> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(target, fs::Permissions::from_mode(mode & 0o7777))?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn set_mode(_target: &Path, _mode: u32) -> Result<(), RPMError> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn chown(target: &Path, uid: u32, gid: u32) -> Result<(), RPMError> {
+    std::os::unix::fs::lchown(target, Some(uid), Some(gid))?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn chown(_target: &Path, _uid: u32, _gid: u32) -> Result<(), RPMError> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(link: &str, target: &Path) -> Result<(), RPMError> {
+    std::os::unix::fs::symlink(link, target)?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn symlink(link: &str, target: &Path) -> Result<(), RPMError> {
+    std::os::windows::fs::symlink_file(link, target)?;
+    Ok(())
+}
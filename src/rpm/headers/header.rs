@@ -181,6 +181,30 @@ where
             })
     }
 
+    pub(crate) fn get_entry_i16_array_data(&self, tag: T) -> Result<Vec<i16>, RPMError> {
+        let entry = self.find_entry_or_err(&tag)?;
+        entry
+            .data
+            .as_i16_array()
+            .ok_or_else(|| RPMError::UnexpectedTagDataType {
+                expected_data_type: "i16 array",
+                actual_data_type: entry.data.to_string(),
+                tag: entry.tag.to_string(),
+            })
+    }
+
+    pub(crate) fn get_entry_i64_array_data(&self, tag: T) -> Result<Vec<i64>, RPMError> {
+        let entry = self.find_entry_or_err(&tag)?;
+        entry
+            .data
+            .as_i64_array()
+            .ok_or_else(|| RPMError::UnexpectedTagDataType {
+                expected_data_type: "i64 array",
+                actual_data_type: entry.data.to_string(),
+                tag: entry.tag.to_string(),
+            })
+    }
+
     pub(crate) fn get_entry_i64_data(&self, tag: T) -> Result<i64, RPMError> {
         let entry = self.find_entry_or_err(&tag)?;
         entry
@@ -253,7 +277,7 @@ impl Header<IndexSignatureTag> {
     ///
     /// Please use the [`builder`](Self::builder()) which has modular and safe API.
     pub(crate) fn new_signature_header(
-        size: i32,
+        size: u64,
         md5sum: &[u8],
         sha1: String,
         rsa_spanning_header: &[u8],
@@ -370,6 +394,424 @@ impl Header<IndexTag> {
             )?;
         Ok(v)
     }
+
+    /// Extract the metadata of all contained files.
+    ///
+    /// The entries are in the same order as the ones returned by [`get_file_names`](Self::get_file_names).
+    pub fn get_file_entries(&self) -> Result<Vec<FileEntry>, RPMError> {
+        // packages without any files do not carry any of the file tags
+        if self.find_entry_or_err(&IndexTag::RPMTAG_BASENAMES).is_err() {
+            return Ok(Vec::new());
+        }
+        let paths = self.get_file_names()?;
+        let n = paths.len();
+
+        // large packages store their file sizes as 64 bit values
+        let sizes: Vec<u64> = match self.get_entry_i32_array_data(IndexTag::RPMTAG_FILESIZES) {
+            Ok(sizes) => sizes.into_iter().map(|size| size as u32 as u64).collect(),
+            Err(RPMError::TagNotFound(_)) => self
+                .get_entry_i64_array_data(IndexTag::RPMTAG_LONGFILESIZES)?
+                .into_iter()
+                .map(|size| size as u64)
+                .collect(),
+            Err(e) => return Err(e),
+        };
+        let sizes = ensure_len(IndexTag::RPMTAG_FILESIZES, sizes, n)?;
+        let modes = ensure_len(
+            IndexTag::RPMTAG_FILEMODES,
+            self.get_entry_i16_array_data(IndexTag::RPMTAG_FILEMODES)?,
+            n,
+        )?;
+        let mtimes = ensure_len(
+            IndexTag::RPMTAG_FILEMTIMES,
+            self.get_entry_i32_array_data(IndexTag::RPMTAG_FILEMTIMES)?,
+            n,
+        )?;
+        let digests = ensure_len(
+            IndexTag::RPMTAG_FILEDIGESTS,
+            self.get_entry_string_array_data(IndexTag::RPMTAG_FILEDIGESTS)?,
+            n,
+        )?;
+        let links = ensure_len(
+            IndexTag::RPMTAG_FILELINKTOS,
+            self.get_entry_string_array_data(IndexTag::RPMTAG_FILELINKTOS)?,
+            n,
+        )?;
+        let flags = ensure_len(
+            IndexTag::RPMTAG_FILEFLAGS,
+            self.get_entry_i32_array_data(IndexTag::RPMTAG_FILEFLAGS)?,
+            n,
+        )?;
+        // verifying everything is the default
+        let verify_flags = match self.get_entry_i32_array_data(IndexTag::RPMTAG_FILEVERIFYFLAGS) {
+            Ok(verify_flags) => ensure_len(IndexTag::RPMTAG_FILEVERIFYFLAGS, verify_flags, n)?,
+            Err(RPMError::TagNotFound(_)) => vec![RPMVERIFY_ALL as i32; n],
+            Err(e) => return Err(e),
+        };
+        let users = ensure_len(
+            IndexTag::RPMTAG_FILEUSERNAME,
+            self.get_entry_string_array_data(IndexTag::RPMTAG_FILEUSERNAME)?,
+            n,
+        )?;
+        let groups = ensure_len(
+            IndexTag::RPMTAG_FILEGROUPNAME,
+            self.get_entry_string_array_data(IndexTag::RPMTAG_FILEGROUPNAME)?,
+            n,
+        )?;
+        let inodes = ensure_len(
+            IndexTag::RPMTAG_FILEINODES,
+            self.get_entry_i32_array_data(IndexTag::RPMTAG_FILEINODES)?,
+            n,
+        )?;
+
+        let entries = paths
+            .into_iter()
+            .enumerate()
+            .map(|(i, path)| FileEntry {
+                path,
+                size: sizes[i],
+                mode: modes[i] as u16 as u32,
+                modified_at: mtimes[i] as u32,
+                digest: digests[i].to_owned(),
+                link: links[i].to_owned(),
+                flags: FileFlags::from_bits_truncate(flags[i] as u32),
+                verify_flags: FileVerifyFlags::from_bits_truncate(verify_flags[i] as u32),
+                user: users[i].to_owned(),
+                group: groups[i].to_owned(),
+                inode: inodes[i] as u32,
+            })
+            .collect();
+        Ok(entries)
+    }
+
+    /// Extract the paths of all regular files sharing their inode with other files.
+    ///
+    /// The members of each set are in the order of the header. Only the last one
+    /// carries the content within the payload.
+    pub fn get_hardlink_sets(&self) -> Result<Vec<Vec<PathBuf>>, RPMError> {
+        let entries = self.get_file_entries()?;
+        let devices = match self.get_entry_i32_array_data(IndexTag::RPMTAG_FILEDEVICES) {
+            Ok(devices) => ensure_len(IndexTag::RPMTAG_FILEDEVICES, devices, entries.len())?,
+            Err(RPMError::TagNotFound(_)) => vec![0; entries.len()],
+            Err(e) => return Err(e),
+        };
+
+        let mut sets = Vec::<Vec<PathBuf>>::new();
+        let mut set_indices = std::collections::HashMap::new();
+        for (entry, device) in entries.into_iter().zip(devices) {
+            if entry.mode & S_IFMT != S_IFREG {
+                continue;
+            }
+            let index = *set_indices.entry((device, entry.inode)).or_insert_with(|| {
+                sets.push(Vec::new());
+                sets.len() - 1
+            });
+            sets[index].push(entry.path);
+        }
+        sets.retain(|set| set.len() > 1);
+        Ok(sets)
+    }
+
+    pub fn get_requires(&self) -> Result<Vec<Dependency>, RPMError> {
+        self.get_dependencies(
+            IndexTag::RPMTAG_REQUIRENAME,
+            IndexTag::RPMTAG_REQUIREVERSION,
+            IndexTag::RPMTAG_REQUIREFLAGS,
+        )
+    }
+
+    pub fn get_provides(&self) -> Result<Vec<Dependency>, RPMError> {
+        self.get_dependencies(
+            IndexTag::RPMTAG_PROVIDENAME,
+            IndexTag::RPMTAG_PROVIDEVERSION,
+            IndexTag::RPMTAG_PROVIDEFLAGS,
+        )
+    }
+
+    pub fn get_conflicts(&self) -> Result<Vec<Dependency>, RPMError> {
+        self.get_dependencies(
+            IndexTag::RPMTAG_CONFLICTNAME,
+            IndexTag::RPMTAG_CONFLICTVERSION,
+            IndexTag::RPMTAG_CONFLICTFLAGS,
+        )
+    }
+
+    pub fn get_obsoletes(&self) -> Result<Vec<Dependency>, RPMError> {
+        self.get_dependencies(
+            IndexTag::RPMTAG_OBSOLETENAME,
+            IndexTag::RPMTAG_OBSOLETEVERSION,
+            IndexTag::RPMTAG_OBSOLETEFLAGS,
+        )
+    }
+
+    /// Weak dependencies installed along by default.
+    pub fn get_recommends(&self) -> Result<Vec<Dependency>, RPMError> {
+        self.get_dependencies(
+            IndexTag::RPMTAG_RECOMMENDNAME,
+            IndexTag::RPMTAG_RECOMMENDVERSION,
+            IndexTag::RPMTAG_RECOMMENDFLAGS,
+        )
+    }
+
+    /// Weak dependencies merely hinted at.
+    pub fn get_suggests(&self) -> Result<Vec<Dependency>, RPMError> {
+        self.get_dependencies(
+            IndexTag::RPMTAG_SUGGESTNAME,
+            IndexTag::RPMTAG_SUGGESTVERSION,
+            IndexTag::RPMTAG_SUGGESTFLAGS,
+        )
+    }
+
+    /// Packages this one is installed along with by default.
+    pub fn get_supplements(&self) -> Result<Vec<Dependency>, RPMError> {
+        self.get_dependencies(
+            IndexTag::RPMTAG_SUPPLEMENTNAME,
+            IndexTag::RPMTAG_SUPPLEMENTVERSION,
+            IndexTag::RPMTAG_SUPPLEMENTFLAGS,
+        )
+    }
+
+    /// Packages this one is hinted at for.
+    pub fn get_enhances(&self) -> Result<Vec<Dependency>, RPMError> {
+        self.get_dependencies(
+            IndexTag::RPMTAG_ENHANCENAME,
+            IndexTag::RPMTAG_ENHANCEVERSION,
+            IndexTag::RPMTAG_ENHANCEFLAGS,
+        )
+    }
+
+    /// Combine the names, versions and flags of a dependency kind, which are all
+    /// absent if the package has no such dependencies.
+    fn get_dependencies(
+        &self,
+        name_tag: IndexTag,
+        version_tag: IndexTag,
+        flags_tag: IndexTag,
+    ) -> Result<Vec<Dependency>, RPMError> {
+        let names = match self.get_entry_string_array_data(name_tag) {
+            Ok(names) => names,
+            Err(RPMError::TagNotFound(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let versions = ensure_len(
+            version_tag,
+            self.get_entry_string_array_data(version_tag)?,
+            names.len(),
+        )?;
+        let flags = ensure_len(
+            flags_tag,
+            self.get_entry_i32_array_data(flags_tag)?,
+            names.len(),
+        )?;
+        Ok(names
+            .iter()
+            .zip(versions)
+            .zip(flags)
+            .map(|((name, version), flags)| {
+                Dependency::new(
+                    name.to_owned(),
+                    DependencyFlags::from_bits_truncate(flags as u32),
+                    version.to_owned(),
+                )
+            })
+            .collect())
+    }
+
+    pub fn get_pre_install_script(&self) -> Result<Option<Scriptlet>, RPMError> {
+        self.get_scriptlet(
+            IndexTag::RPMTAG_PREIN,
+            IndexTag::RPMTAG_PREINPROG,
+            IndexTag::RPMTAG_PREINFLAGS,
+        )
+    }
+
+    pub fn get_post_install_script(&self) -> Result<Option<Scriptlet>, RPMError> {
+        self.get_scriptlet(
+            IndexTag::RPMTAG_POSTIN,
+            IndexTag::RPMTAG_POSTINPROG,
+            IndexTag::RPMTAG_POSTINFLAGS,
+        )
+    }
+
+    pub fn get_pre_uninstall_script(&self) -> Result<Option<Scriptlet>, RPMError> {
+        self.get_scriptlet(
+            IndexTag::RPMTAG_PREUN,
+            IndexTag::RPMTAG_PREUNPROG,
+            IndexTag::RPMTAG_PREUNFLAGS,
+        )
+    }
+
+    pub fn get_post_uninstall_script(&self) -> Result<Option<Scriptlet>, RPMError> {
+        self.get_scriptlet(
+            IndexTag::RPMTAG_POSTUN,
+            IndexTag::RPMTAG_POSTUNPROG,
+            IndexTag::RPMTAG_POSTUNFLAGS,
+        )
+    }
+
+    /// `%pretrans`, run once before the transaction containing the package.
+    pub fn get_pre_transaction_script(&self) -> Result<Option<Scriptlet>, RPMError> {
+        self.get_scriptlet(
+            IndexTag::RPMTAG_PRETRANS,
+            IndexTag::RPMTAG_PRETRANSPROG,
+            IndexTag::RPMTAG_PRETRANSFLAGS,
+        )
+    }
+
+    /// `%posttrans`, run once after the transaction containing the package.
+    pub fn get_post_transaction_script(&self) -> Result<Option<Scriptlet>, RPMError> {
+        self.get_scriptlet(
+            IndexTag::RPMTAG_POSTTRANS,
+            IndexTag::RPMTAG_POSTTRANSPROG,
+            IndexTag::RPMTAG_POSTTRANSFLAGS,
+        )
+    }
+
+    /// `%verifyscript`, run by `rpm -V`.
+    pub fn get_verify_script(&self) -> Result<Option<Scriptlet>, RPMError> {
+        self.get_scriptlet(
+            IndexTag::RPMTAG_VERIFYSCRIPT,
+            IndexTag::RPMTAG_VERIFYSCRIPTPROG,
+            IndexTag::RPMTAG_VERIFYSCRIPTFLAGS,
+        )
+    }
+
+    /// Scriptlets run when other packages are installed or removed.
+    pub fn get_triggers(&self) -> Result<Vec<Trigger>, RPMError> {
+        let scripts = match self.get_entry_string_array_data(IndexTag::RPMTAG_TRIGGERSCRIPTS) {
+            Ok(scripts) => scripts,
+            Err(RPMError::TagNotFound(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let programs = match self.get_entry_string_array_data(IndexTag::RPMTAG_TRIGGERSCRIPTPROG) {
+            Ok(programs) => {
+                ensure_len(IndexTag::RPMTAG_TRIGGERSCRIPTPROG, programs, scripts.len())?.to_vec()
+            }
+            Err(RPMError::TagNotFound(_)) => vec!["/bin/sh".to_string(); scripts.len()],
+            Err(e) => return Err(e),
+        };
+        let script_flags = match self.get_entry_i32_array_data(IndexTag::RPMTAG_TRIGGERSCRIPTFLAGS)
+        {
+            Ok(flags) => ensure_len(IndexTag::RPMTAG_TRIGGERSCRIPTFLAGS, flags, scripts.len())?,
+            Err(RPMError::TagNotFound(_)) => vec![0; scripts.len()],
+            Err(e) => return Err(e),
+        };
+        let conditions = self.get_dependencies(
+            IndexTag::RPMTAG_TRIGGERNAME,
+            IndexTag::RPMTAG_TRIGGERVERSION,
+            IndexTag::RPMTAG_TRIGGERFLAGS,
+        )?;
+        let indices = ensure_len(
+            IndexTag::RPMTAG_TRIGGERINDEX,
+            self.get_entry_i32_array_data(IndexTag::RPMTAG_TRIGGERINDEX)?,
+            conditions.len(),
+        )?;
+
+        let mut triggers = Vec::<Option<Trigger>>::new();
+        triggers.resize(scripts.len(), None);
+        for (mut condition, index) in conditions.into_iter().zip(indices) {
+            let trigger_type = TriggerType::from_sense(condition.sense).ok_or_else(|| {
+                RPMError::InvalidTrigger(format!("no type given for {}", condition.dep_name))
+            })?;
+            condition.sense -= trigger_type.sense();
+            let trigger =
+                triggers
+                    .get_mut(index as usize)
+                    .ok_or_else(|| RPMError::InvalidTagIndex {
+                        tag: IndexTag::RPMTAG_TRIGGERINDEX.to_string(),
+                        index: index as u32,
+                        bound: scripts.len() as u32,
+                    })?;
+            match trigger {
+                Some(trigger) => trigger.conditions.push(condition),
+                None => {
+                    let index = index as usize;
+                    let scriptlet = Scriptlet::new(scripts[index].clone())
+                        .with_interpreter(programs[index].clone())
+                        .with_flags(ScriptletFlags::from_bits_truncate(
+                            script_flags[index] as u32,
+                        ));
+                    *trigger = Some(Trigger::new(trigger_type, condition, scriptlet));
+                }
+            }
+        }
+        // scripts without any condition are never run
+        Ok(triggers.into_iter().flatten().collect())
+    }
+
+    /// Combine the body, interpreter and flags of a scriptlet, `None` if the package
+    /// has no such scriptlet.
+    ///
+    /// The body is empty for scriptlets which only run the interpreter, such as
+    /// `%post -p /sbin/ldconfig`.
+    fn get_scriptlet(
+        &self,
+        script_tag: IndexTag,
+        program_tag: IndexTag,
+        flags_tag: IndexTag,
+    ) -> Result<Option<Scriptlet>, RPMError> {
+        let body = match self.get_entry_string_data(script_tag) {
+            Ok(body) => body,
+            Err(RPMError::TagNotFound(_)) if self.find_entry_or_err(&program_tag).is_ok() => "",
+            Err(RPMError::TagNotFound(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut scriptlet = Scriptlet::new(body);
+        match self.find_entry_or_err(&program_tag) {
+            Ok(entry) => {
+                // a plain string without arguments, a string array with them
+                let program = match &entry.data {
+                    IndexData::StringTag(interpreter) => std::slice::from_ref(interpreter),
+                    IndexData::StringArray(program) if !program.is_empty() => program,
+                    data => {
+                        return Err(RPMError::UnexpectedTagDataType {
+                            expected_data_type: "string or string array",
+                            actual_data_type: data.to_string(),
+                            tag: entry.tag.to_string(),
+                        })
+                    }
+                };
+                scriptlet.interpreter = program[0].clone();
+                scriptlet.arguments = program[1..].to_vec();
+            }
+            Err(RPMError::TagNotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+        match self.get_entry_i32_data(flags_tag) {
+            Ok(flags) => scriptlet.flags = ScriptletFlags::from_bits_truncate(flags as u32),
+            Err(RPMError::TagNotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+        Ok(Some(scriptlet))
+    }
+
+    /// Parse the rich dependencies among the names of a dependency tag such as
+    /// `RPMTAG_REQUIRENAME`, skipping the simple ones.
+    pub fn get_rich_dependencies(&self, tag: IndexTag) -> Result<Vec<RichDependency>, RPMError> {
+        let names = match self.get_entry_string_array_data(tag) {
+            Ok(names) => names,
+            Err(RPMError::TagNotFound(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        names
+            .iter()
+            .filter(|name| name.starts_with('('))
+            .map(|name| name.parse())
+            .collect()
+    }
+}
+
+/// Make sure a per file array tag has an item for each of the `n` files.
+fn ensure_len<V: AsRef<[I]>, I>(tag: IndexTag, items: V, n: usize) -> Result<V, RPMError> {
+    let len = items.as_ref().len();
+    if len < n {
+        return Err(RPMError::InvalidTagIndex {
+            tag: tag.to_string(),
+            index: (n - 1) as u32,
+            bound: len as u32,
+        });
+    }
+    Ok(items)
 }
 
 fn parse_entry_data_number<'a, T, E, F>(
@@ -437,7 +879,7 @@ mod tests2 {
         };
 
         let built = Header::<IndexSignatureTag>::new_signature_header(
-            size,
+            size as u64,
             md5sum,
             sha1,
             rsa_spanning_header,
@@ -767,6 +1209,20 @@ impl IndexData {
         }
     }
 
+    pub fn as_i16_array(&self) -> Option<Vec<i16>> {
+        match self {
+            IndexData::Int16(s) => Some(s.to_vec()),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64_array(&self) -> Option<Vec<i64>> {
+        match self {
+            IndexData::Int64(s) => Some(s.to_vec()),
+            _ => None,
+        }
+    }
+
     pub fn as_i64(&self) -> Option<i64> {
         match self {
             IndexData::Int64(s) => {
@@ -0,0 +1,353 @@
+//! Rich or boolean dependencies as supported since rpm 4.13.
+//!
+//! See <https://rpm-software-management.github.io/rpm/manual/boolean_dependencies.html>.
+
+use std::fmt;
+
+use super::{Dependency, DependencyFlags};
+use crate::errors::*;
+
+/// Boolean expression of dependencies, such as `(foo >= 1.0 with foo < 2.0)`.
+///
+/// Turned into a regular [`Dependency`](super::Dependency) with [`Dependency::rich`](super::Dependency::rich).
+#[derive(Debug, Clone, PartialEq)]
+pub enum RichDependency {
+    /// `name` or `name op version`
+    Simple(Dependency),
+    /// all of the operands
+    And(Vec<RichDependency>),
+    /// at least one of the operands
+    Or(Vec<RichDependency>),
+    /// `then` if `condition` is fulfilled, `otherwise` if not
+    If {
+        then: Box<RichDependency>,
+        condition: Box<RichDependency>,
+        otherwise: Option<Box<RichDependency>>,
+    },
+    /// `then` unless `condition` is fulfilled, `otherwise` if it is
+    Unless {
+        then: Box<RichDependency>,
+        condition: Box<RichDependency>,
+        otherwise: Option<Box<RichDependency>>,
+    },
+    /// all operands fulfilled by a single package
+    With(Vec<RichDependency>),
+    /// the first operand fulfilled by a package which does not fulfill the second
+    Without(Box<RichDependency>, Box<RichDependency>),
+}
+
+impl RichDependency {
+    /// Make sure the expression can be expressed and understood by rpm.
+    pub fn validate(&self) -> Result<(), RPMError> {
+        match self {
+            RichDependency::Simple(dependency) => validate_simple(dependency),
+            RichDependency::And(operands) | RichDependency::Or(operands) => {
+                if operands.len() < 2 {
+                    return Err(invalid(format!(
+                        "{} needs at least two operands",
+                        self.operator()
+                    )));
+                }
+                operands.iter().try_for_each(RichDependency::validate)
+            }
+            RichDependency::With(operands) => {
+                if operands.len() < 2 {
+                    return Err(invalid("with needs at least two operands"));
+                }
+                operands.iter().try_for_each(validate_single_package)
+            }
+            RichDependency::Without(dependency, excluded) => {
+                validate_single_package(dependency)?;
+                validate_single_package(excluded)
+            }
+            RichDependency::If {
+                then,
+                condition,
+                otherwise,
+            }
+            | RichDependency::Unless {
+                then,
+                condition,
+                otherwise,
+            } => {
+                then.validate()?;
+                condition.validate()?;
+                otherwise
+                    .iter()
+                    .try_for_each(|otherwise| otherwise.validate())
+            }
+        }
+    }
+
+    fn operator(&self) -> &'static str {
+        match self {
+            RichDependency::Simple(_) => "",
+            RichDependency::And(_) => "and",
+            RichDependency::Or(_) => "or",
+            RichDependency::If { .. } => "if",
+            RichDependency::Unless { .. } => "unless",
+            RichDependency::With(_) => "with",
+            RichDependency::Without(..) => "without",
+        }
+    }
+}
+
+/// Serializes the expression as stored in a header, without any validation.
+impl fmt::Display for RichDependency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RichDependency::Simple(dependency) => {
+                write!(f, "{}", dependency.dep_name)?;
+                if let Some(operator) = comparison_operator(dependency.sense) {
+                    write!(f, " {} {}", operator, dependency.version)?;
+                }
+                Ok(())
+            }
+            RichDependency::And(operands)
+            | RichDependency::Or(operands)
+            | RichDependency::With(operands) => {
+                write!(f, "(")?;
+                for (i, operand) in operands.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " {} ", self.operator())?;
+                    }
+                    write!(f, "{}", operand)?;
+                }
+                write!(f, ")")
+            }
+            RichDependency::If {
+                then,
+                condition,
+                otherwise,
+            }
+            | RichDependency::Unless {
+                then,
+                condition,
+                otherwise,
+            } => {
+                write!(f, "({} {} {}", then, self.operator(), condition)?;
+                if let Some(otherwise) = otherwise {
+                    write!(f, " else {}", otherwise)?;
+                }
+                write!(f, ")")
+            }
+            RichDependency::Without(dependency, excluded) => {
+                write!(f, "({} without {})", dependency, excluded)
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for RichDependency {
+    type Err = RPMError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser { rest: raw.trim() };
+        if !parser.rest.starts_with('(') {
+            return Err(invalid(format!("{} does not start with (", raw)));
+        }
+        let dependency = parser.parse_operand()?;
+        if !parser.rest.is_empty() {
+            return Err(invalid(format!("unexpected {} after {}", parser.rest, raw)));
+        }
+        dependency.validate()?;
+        Ok(dependency)
+    }
+}
+
+impl From<Dependency> for RichDependency {
+    fn from(dependency: Dependency) -> Self {
+        RichDependency::Simple(dependency)
+    }
+}
+
+fn invalid<T: Into<String>>(desc: T) -> RPMError {
+    RPMError::InvalidRichDependency(desc.into())
+}
+
+fn validate_simple(dependency: &Dependency) -> Result<(), RPMError> {
+    let name = &dependency.dep_name;
+    if name.is_empty() || name.starts_with('(') || name.contains(char::is_whitespace) {
+        return Err(invalid(format!("invalid dependency name {:?}", name)));
+    }
+    let has_version = comparison_operator(dependency.sense).is_some();
+    let version = &dependency.version;
+    if has_version && (version.is_empty() || version.contains(char::is_whitespace)) {
+        return Err(invalid(format!(
+            "invalid version {:?} of {}",
+            version, name
+        )));
+    }
+    if !has_version && !version.is_empty() {
+        return Err(invalid(format!("version of {} without operator", name)));
+    }
+    Ok(())
+}
+
+/// Operands of `with` and `without` have to describe a single package.
+fn validate_single_package(operand: &RichDependency) -> Result<(), RPMError> {
+    match operand {
+        RichDependency::Simple(dependency) => validate_simple(dependency),
+        _ => Err(invalid(format!(
+            "{} is not allowed within with or without",
+            operand.operator()
+        ))),
+    }
+}
+
+const COMPARISON_OPERATORS: [(&str, DependencyFlags); 5] = [
+    ("<=", DependencyFlags::LESS.union(DependencyFlags::EQUAL)),
+    (">=", DependencyFlags::GREATER.union(DependencyFlags::EQUAL)),
+    ("<", DependencyFlags::LESS),
+    (">", DependencyFlags::GREATER),
+    ("=", DependencyFlags::EQUAL),
+];
+
+fn comparison_operator(sense: DependencyFlags) -> Option<&'static str> {
+    let sense = sense & (DependencyFlags::LESS | DependencyFlags::GREATER | DependencyFlags::EQUAL);
+    COMPARISON_OPERATORS
+        .iter()
+        .find(|(_, operator_sense)| *operator_sense == sense)
+        .map(|(operator, _)| *operator)
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    /// A nested expression or a simple dependency.
+    fn parse_operand(&mut self) -> Result<RichDependency, RPMError> {
+        self.skip_whitespace();
+        if let Some(rest) = self.rest.strip_prefix('(') {
+            self.rest = rest;
+            self.parse_expression()
+        } else {
+            self.parse_simple()
+        }
+    }
+
+    /// The content of a parenthesized expression, including the closing parenthesis.
+    fn parse_expression(&mut self) -> Result<RichDependency, RPMError> {
+        let first = self.parse_operand()?;
+        let operator = self.parse_word();
+        let expression = match operator {
+            // superfluous parentheses
+            "" => first,
+            "and" | "or" | "with" => {
+                let mut operands = vec![first, self.parse_operand()?];
+                loop {
+                    match self.parse_word() {
+                        "" => break,
+                        next if next == operator => operands.push(self.parse_operand()?),
+                        next => {
+                            return Err(invalid(format!(
+                                "{} and {} cannot be mixed without parentheses",
+                                operator, next
+                            )))
+                        }
+                    }
+                }
+                match operator {
+                    "and" => RichDependency::And(operands),
+                    "or" => RichDependency::Or(operands),
+                    _ => RichDependency::With(operands),
+                }
+            }
+            "if" | "unless" => {
+                let then = Box::new(first);
+                let condition = Box::new(self.parse_operand()?);
+                let otherwise = match self.parse_word() {
+                    "" => None,
+                    "else" => Some(Box::new(self.parse_operand()?)),
+                    next => return Err(invalid(format!("unexpected {} after {}", next, operator))),
+                };
+                if operator == "if" {
+                    RichDependency::If {
+                        then,
+                        condition,
+                        otherwise,
+                    }
+                } else {
+                    RichDependency::Unless {
+                        then,
+                        condition,
+                        otherwise,
+                    }
+                }
+            }
+            "without" => RichDependency::Without(Box::new(first), Box::new(self.parse_operand()?)),
+            unknown => return Err(invalid(format!("unknown operator {}", unknown))),
+        };
+        self.skip_whitespace();
+        match self.rest.strip_prefix(')') {
+            Some(rest) => self.rest = rest,
+            None => return Err(invalid("missing )")),
+        }
+        Ok(expression)
+    }
+
+    /// An operator keyword, empty at the end of an expression.
+    fn parse_word(&mut self) -> &'a str {
+        self.skip_whitespace();
+        let end = self
+            .rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(self.rest.len());
+        let (word, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        word
+    }
+
+    fn parse_simple(&mut self) -> Result<RichDependency, RPMError> {
+        let name = self.parse_token();
+        if name.is_empty() {
+            return Err(invalid("missing dependency name"));
+        }
+        self.skip_whitespace();
+        let operator = COMPARISON_OPERATORS
+            .iter()
+            .find(|(operator, _)| self.rest.starts_with(operator));
+        let (sense, version) = match operator {
+            Some((operator, sense)) => {
+                self.rest = &self.rest[operator.len()..];
+                self.skip_whitespace();
+                (*sense, self.parse_token())
+            }
+            None => (DependencyFlags::ANY, ""),
+        };
+        Ok(RichDependency::Simple(Dependency::new(
+            name.to_string(),
+            sense,
+            version.to_string(),
+        )))
+    }
+
+    /// A name or version, which ends at whitespace or an unbalanced closing parenthesis.
+    fn parse_token(&mut self) -> &'a str {
+        let mut depth = 0;
+        let mut end = self.rest.len();
+        for (i, c) in self.rest.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' if depth == 0 => {
+                    end = i;
+                    break;
+                }
+                ')' => depth -= 1,
+                c if c.is_whitespace() => {
+                    end = i;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let (token, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        token
+    }
+}
@@ -50,15 +50,35 @@ where
     T: ConstructionStage,
 {
     /// Construct the complete signature header.
-    pub fn build(mut self, signature_size: i32) -> Header<IndexSignatureTag> {
-        self.entries.insert(
-            0,
+    ///
+    /// The combined size of header and payload is recorded as `RPMSIGTAG_LONGSIZE`
+    /// if it exceeds the 32 bit `RPMSIGTAG_SIZE`.
+    pub fn build(self, header_and_content_len: u64) -> Header<IndexSignatureTag> {
+        let long_size = header_and_content_len > u32::MAX as u64;
+        self.build_with_size(header_and_content_len, long_size)
+    }
+
+    /// Construct the complete signature header with a size tag of the given width,
+    /// `header_and_content_len` must fit into it.
+    pub(crate) fn build_with_size(
+        mut self,
+        header_and_content_len: u64,
+        long_size: bool,
+    ) -> Header<IndexSignatureTag> {
+        let size_entry = if long_size {
+            IndexEntry::new(
+                IndexSignatureTag::RPMSIGTAG_LONGSIZE,
+                0i32, // externally filled
+                IndexData::Int64(vec![header_and_content_len as i64]),
+            )
+        } else {
             IndexEntry::new(
                 IndexSignatureTag::RPMSIGTAG_SIZE,
                 0i32, // externally filled
-                IndexData::Int32(vec![signature_size]),
-            ),
-        );
+                IndexData::Int32(vec![header_and_content_len as u32 as i32]),
+            )
+        };
+        self.entries.insert(0, size_entry);
 
         Header::<IndexSignatureTag>::from_entries(
             self.entries,
@@ -132,7 +152,7 @@ mod test {
         let header = builder
             .add_digest("", &digest_header_and_archive[..])
             .add_signature(&rsa_sig_header_only[..], &rsa_sig_header_and_archive[..])
-            .build(32i32);
+            .build(32);
 
         assert!(header
             .find_entry_or_err(&IndexSignatureTag::RPMSIGTAG_RSA)
@@ -147,4 +167,31 @@ mod test {
             .find_entry_or_err(&IndexSignatureTag::RPMSIGTAG_SHA1)
             .is_ok());
     }
+
+    #[test]
+    fn signature_builder_long_size() {
+        let build = |len: u64| {
+            SignatureHeaderBuilder::<Empty>::new()
+                .add_digest("", &[0u8; 16])
+                .build(len)
+        };
+
+        let header = build(u32::MAX as u64);
+        let size = header
+            .find_entry_or_err(&IndexSignatureTag::RPMSIGTAG_SIZE)
+            .unwrap();
+        assert_eq!(IndexData::Int32(vec![-1]), size.data);
+        assert!(header
+            .find_entry_or_err(&IndexSignatureTag::RPMSIGTAG_LONGSIZE)
+            .is_err());
+
+        let header = build(6 << 30);
+        let size = header
+            .find_entry_or_err(&IndexSignatureTag::RPMSIGTAG_LONGSIZE)
+            .unwrap();
+        assert_eq!(IndexData::Int64(vec![6 << 30]), size.data);
+        assert!(header
+            .find_entry_or_err(&IndexSignatureTag::RPMSIGTAG_SIZE)
+            .is_err());
+    }
 }
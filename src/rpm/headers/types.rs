@@ -1,20 +1,136 @@
 //! A collection of types used in various header records.
 
+use super::RichDependency;
 use crate::constants::*;
+use crate::errors::RPMError;
+use std::path::PathBuf;
 
 /// Describes a file present in the rpm file.
 pub struct RPMFileEntry {
-    pub(crate) size: i32,
+    pub(crate) size: u64,
     pub(crate) mode: i16,
-    pub(crate) modified_at: i32,
+    /// `None` for the default of the builder
+    pub(crate) modified_at: Option<u32>,
     pub(crate) sha_checksum: String,
     pub(crate) link: String,
     pub(crate) flag: i32,
+    pub(crate) verify_flags: u32,
+    /// major and minor number of device nodes
+    pub(crate) rdev: (u32, u32),
+    pub(crate) uid: Option<u32>,
+    pub(crate) gid: Option<u32>,
     pub(crate) user: String,
     pub(crate) group: String,
     pub(crate) base_name: String,
     pub(crate) dir: String,
-    pub(crate) content: Option<Vec<u8>>,
+    pub(crate) content: RPMFileContent,
+}
+
+/// Type of a file without any content, see [`RPMBuilder::with_special_file`](crate::RPMBuilder::with_special_file).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpecialFile {
+    CharDevice {
+        major: u32,
+        minor: u32,
+    },
+    BlockDevice {
+        major: u32,
+        minor: u32,
+    },
+    /// named pipe
+    Fifo,
+    Socket,
+}
+
+impl SpecialFile {
+    pub(crate) fn file_type(&self) -> u32 {
+        match self {
+            SpecialFile::CharDevice { .. } => S_IFCHR,
+            SpecialFile::BlockDevice { .. } => S_IFBLK,
+            SpecialFile::Fifo => S_IFIFO,
+            SpecialFile::Socket => S_IFSOCK,
+        }
+    }
+
+    pub(crate) fn rdev(&self) -> (u32, u32) {
+        match *self {
+            SpecialFile::CharDevice { major, minor }
+            | SpecialFile::BlockDevice { major, minor } => (major, minor),
+            SpecialFile::Fifo | SpecialFile::Socket => (0, 0),
+        }
+    }
+}
+
+/// Origin of the content of a file present in the rpm file.
+pub(crate) enum RPMFileContent {
+    /// Content held in memory.
+    Data(Vec<u8>),
+    /// Content read from disk again once the payload is written.
+    Path(PathBuf),
+    /// Hardlink sharing the content of the file with the given cpio path.
+    Hardlink(String),
+}
+
+bitflags::bitflags! {
+    /// Attributes of a file as recorded in `RPMTAG_FILEFLAGS`.
+    pub struct FileFlags: u32 {
+        /// `%config`
+        const CONFIG = RPMFILE_CONFIG as u32;
+        /// `%doc`
+        const DOC = RPMFILE_DOC as u32;
+        const ICON = RPMFILE_ICON as u32;
+        /// `%config(missingok)`
+        const MISSINGOK = RPMFILE_MISSINGOK as u32;
+        /// `%config(noreplace)`
+        const NOREPLACE = RPMFILE_NOREPLACE as u32;
+        const SPECFILE = RPMFILE_SPECFILE as u32;
+        /// `%ghost`, only recorded in the header but not contained in the payload
+        const GHOST = RPMFILE_GHOST as u32;
+        /// `%license`
+        const LICENSE = RPMFILE_LICENSE as u32;
+        /// `%readme`
+        const README = RPMFILE_README as u32;
+        const PUBKEY = RPMFILE_PUBKEY as u32;
+        /// `%artifact`
+        const ARTIFACT = RPMFILE_ARTIFACT as u32;
+    }
+}
+
+bitflags::bitflags! {
+    /// Attributes of a file checked by `rpm -V`, as recorded in `RPMTAG_FILEVERIFYFLAGS`.
+    pub struct FileVerifyFlags: u32 {
+        const DIGEST = RPMVERIFY_FILEDIGEST;
+        const SIZE = RPMVERIFY_FILESIZE;
+        const LINKTO = RPMVERIFY_LINKTO;
+        const USER = RPMVERIFY_USER;
+        const GROUP = RPMVERIFY_GROUP;
+        const MTIME = RPMVERIFY_MTIME;
+        const MODE = RPMVERIFY_MODE;
+        const RDEV = RPMVERIFY_RDEV;
+        const CAPS = RPMVERIFY_CAPS;
+    }
+}
+
+/// Metadata of a file as recorded in the header of a parsed rpm file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileEntry {
+    /// Absolute path the file is installed to.
+    pub path: PathBuf,
+    pub size: u64,
+    /// File type and permission bits as in `st_mode`.
+    pub mode: u32,
+    /// Modification time in seconds since the epoch.
+    pub modified_at: u32,
+    /// Hex encoded digest of the file content, empty for anything but regular files.
+    pub digest: String,
+    /// Target of a symlink, empty for anything but symlinks.
+    pub link: String,
+    pub flags: FileFlags,
+    /// Attributes checked by `rpm -V`.
+    pub verify_flags: FileVerifyFlags,
+    pub user: String,
+    pub group: String,
+    pub inode: u32,
 }
 
 /// Description of file modes.
@@ -28,6 +144,11 @@ pub struct RPMFileOptions {
     pub(crate) mode: i32,
     pub(crate) flag: i32,
     pub(crate) inherit_permissions: bool,
+    pub(crate) mtime: Option<u32>,
+    pub(crate) verify_flags: u32,
+    pub(crate) rdev: (u32, u32),
+    pub(crate) uid: Option<u32>,
+    pub(crate) gid: Option<u32>,
 }
 
 impl RPMFileOptions {
@@ -41,6 +162,11 @@ impl RPMFileOptions {
                 mode: 0o100_664,
                 flag: 0,
                 inherit_permissions: true,
+                mtime: None,
+                verify_flags: RPMVERIFY_ALL,
+                rdev: (0, 0),
+                uid: None,
+                gid: None,
             },
         }
     }
@@ -61,6 +187,22 @@ impl RPMFileOptionsBuilder {
         self
     }
 
+    /// Numeric user id stored in the payload, instead of the default owner of the builder.
+    ///
+    /// rpm itself resolves the user name on installation.
+    pub fn uid(mut self, uid: u32) -> Self {
+        self.inner.uid = Some(uid);
+        self
+    }
+
+    /// Numeric group id stored in the payload, instead of the default owner of the builder.
+    ///
+    /// rpm itself resolves the group name on installation.
+    pub fn gid(mut self, gid: u32) -> Self {
+        self.inner.gid = Some(gid);
+        self
+    }
+
     pub fn symlink<T: Into<String>>(mut self, symlink: T) -> Self {
         self.inner.symlink = symlink.into();
         self
@@ -72,13 +214,73 @@ impl RPMFileOptionsBuilder {
         self
     }
 
+    /// Modification time in seconds since the epoch, instead of the one of the source file.
+    pub fn mtime(mut self, mtime: u32) -> Self {
+        self.inner.mtime = Some(mtime);
+        self
+    }
+
     pub fn is_doc(mut self) -> Self {
-        self.inner.flag = RPMFILE_DOC;
+        self.inner.flag |= RPMFILE_DOC;
         self
     }
 
     pub fn is_config(mut self) -> Self {
-        self.inner.flag = RPMFILE_CONFIG;
+        self.inner.flag |= RPMFILE_CONFIG;
+        self
+    }
+
+    /// `%config(noreplace)`, modified files are kept on upgrades and the new version is
+    /// installed with an `.rpmnew` suffix.
+    pub fn is_config_noreplace(mut self) -> Self {
+        self.inner.flag |= RPMFILE_CONFIG | RPMFILE_NOREPLACE;
+        self
+    }
+
+    /// `%config(missingok)`, the file may be absent without rpm complaining.
+    pub fn is_config_missingok(mut self) -> Self {
+        self.inner.flag |= RPMFILE_CONFIG | RPMFILE_MISSINGOK;
+        self
+    }
+
+    /// `%ghost`, the file is owned by the package but not contained in the payload.
+    pub fn is_ghost(mut self) -> Self {
+        self.inner.flag |= RPMFILE_GHOST;
+        self
+    }
+
+    pub fn is_license(mut self) -> Self {
+        self.inner.flag |= RPMFILE_LICENSE;
+        self
+    }
+
+    pub fn is_readme(mut self) -> Self {
+        self.inner.flag |= RPMFILE_README;
+        self
+    }
+
+    pub fn is_artifact(mut self) -> Self {
+        self.inner.flag |= RPMFILE_ARTIFACT;
+        self
+    }
+
+    pub fn is_pubkey(mut self) -> Self {
+        self.inner.flag |= RPMFILE_PUBKEY;
+        self
+    }
+
+    /// `%verify(...)`, only the given attributes are checked by `rpm -V`.
+    ///
+    /// All of them are checked by default.
+    pub fn verify(mut self, flags: FileVerifyFlags) -> Self {
+        // just like rpmbuild, keep the bits not assigned yet
+        self.inner.verify_flags = flags.bits() | !FileVerifyFlags::all().bits();
+        self
+    }
+
+    /// Add arbitrary attributes, in addition to the ones set before.
+    pub fn flags(mut self, flags: FileFlags) -> Self {
+        self.inner.flag |= flags.bits() as i32;
         self
     }
 }
@@ -89,10 +291,61 @@ impl Into<RPMFileOptions> for RPMFileOptionsBuilder {
     }
 }
 
+bitflags::bitflags! {
+    /// Comparison and context of a dependency as recorded in the `*FLAGS` tags,
+    /// such as `RPMTAG_REQUIREFLAGS`.
+    pub struct DependencyFlags: u32 {
+        const ANY = RPMSENSE_ANY;
+        const LESS = RPMSENSE_LESS;
+        const GREATER = RPMSENSE_GREATER;
+        const EQUAL = RPMSENSE_EQUAL;
+        /// `Requires(posttrans)`
+        const POSTTRANS = RPMSENSE_POSTTRANS;
+        /// legacy `PreReq`
+        const PREREQ = RPMSENSE_PREREQ;
+        /// `Requires(pretrans)`
+        const PRETRANS = RPMSENSE_PRETRANS;
+        /// `Requires(interp)`, interpreter of a scriptlet
+        const INTERP = RPMSENSE_INTERP;
+        /// `Requires(pre)`
+        const SCRIPT_PRE = RPMSENSE_SCRIPT_PRE;
+        /// `Requires(post)`
+        const SCRIPT_POST = RPMSENSE_SCRIPT_POST;
+        /// `Requires(preun)`
+        const SCRIPT_PREUN = RPMSENSE_SCRIPT_PREUN;
+        /// `Requires(postun)`
+        const SCRIPT_POSTUN = RPMSENSE_SCRIPT_POSTUN;
+        /// `Requires(verify)`
+        const SCRIPT_VERIFY = RPMSENSE_SCRIPT_VERIFY;
+        /// found by the automatic dependency generator
+        const FIND_REQUIRES = RPMSENSE_FIND_REQUIRES;
+        /// found by the automatic dependency generator
+        const FIND_PROVIDES = RPMSENSE_FIND_PROVIDES;
+        /// condition of a `%triggerin`
+        const TRIGGERIN = RPMSENSE_TRIGGERIN;
+        /// condition of a `%triggerun`
+        const TRIGGERUN = RPMSENSE_TRIGGERUN;
+        /// condition of a `%triggerpostun`
+        const TRIGGERPOSTUN = RPMSENSE_TRIGGERPOSTUN;
+        /// the dependency is not enforced
+        const MISSINGOK = RPMSENSE_MISSINGOK;
+        /// capability of rpm itself such as `rpmlib(PayloadIsXz)`
+        const RPMLIB = RPMSENSE_RPMLIB;
+        /// condition of a `%triggerprein`
+        const TRIGGERPREIN = RPMSENSE_TRIGGERPREIN;
+        const KEYRING = RPMSENSE_KEYRING;
+        /// `config(name)` dependency of packages with config files
+        const CONFIG = RPMSENSE_CONFIG;
+        /// `Requires(meta)`, not affecting the order of installation
+        const META = RPMSENSE_META;
+    }
+}
+
 /// Description of a dependency as present in a RPM header record.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Dependency {
     pub(crate) dep_name: String,
-    pub(crate) sense: u32,
+    pub(crate) sense: DependencyFlags,
     pub(crate) version: String,
 }
 
@@ -102,7 +355,7 @@ impl Dependency {
         T: Into<String>,
         E: Into<String>,
     {
-        Self::new(dep_name.into(), RPMSENSE_LESS, version.into())
+        Self::new(dep_name.into(), DependencyFlags::LESS, version.into())
     }
 
     pub fn less_eq<E, T>(dep_name: T, version: E) -> Self
@@ -112,7 +365,7 @@ impl Dependency {
     {
         Self::new(
             dep_name.into(),
-            RPMSENSE_LESS | RPMSENSE_EQUAL,
+            DependencyFlags::LESS | DependencyFlags::EQUAL,
             version.into(),
         )
     }
@@ -122,7 +375,7 @@ impl Dependency {
         T: Into<String>,
         E: Into<String>,
     {
-        Self::new(dep_name.into(), RPMSENSE_EQUAL, version.into())
+        Self::new(dep_name.into(), DependencyFlags::EQUAL, version.into())
     }
 
     pub fn greater<E, T>(dep_name: T, version: E) -> Self
@@ -130,7 +383,7 @@ impl Dependency {
         T: Into<String>,
         E: Into<String>,
     {
-        Self::new(dep_name.into(), RPMSENSE_GREATER, version.into())
+        Self::new(dep_name.into(), DependencyFlags::GREATER, version.into())
     }
 
     pub fn greater_eq<E, T>(dep_name: T, version: E) -> Self
@@ -140,7 +393,7 @@ impl Dependency {
     {
         Self::new(
             dep_name.into(),
-            RPMSENSE_GREATER | RPMSENSE_EQUAL,
+            DependencyFlags::GREATER | DependencyFlags::EQUAL,
             version.into(),
         )
     }
@@ -149,10 +402,67 @@ impl Dependency {
     where
         T: Into<String>,
     {
-        Self::new(dep_name.into(), RPMSENSE_ANY, "".to_string())
+        Self::new(dep_name.into(), DependencyFlags::ANY, "".to_string())
+    }
+
+    /// A capability of rpm itself, such as `rpmlib(PayloadIsXz)`.
+    pub fn rpm_lib<E, T>(dep_name: T, version: E) -> Self
+    where
+        T: Into<String>,
+        E: Into<String>,
+    {
+        Self::new(
+            dep_name.into(),
+            DependencyFlags::RPMLIB | DependencyFlags::LESS | DependencyFlags::EQUAL,
+            version.into(),
+        )
+    }
+
+    /// Name of the required or provided capability.
+    pub fn name(&self) -> &str {
+        &self.dep_name
+    }
+
+    /// Version compared against, empty if any version matches.
+    pub fn version(&self) -> &str {
+        &self.version
     }
 
-    fn new(dep_name: String, sense: u32, version: String) -> Self {
+    /// Comparison and context of the dependency.
+    pub fn flags(&self) -> DependencyFlags {
+        self.sense
+    }
+
+    /// Add context to the dependency, in addition to the comparison.
+    ///
+    /// For example `Requires(pre): shadow-utils` is
+    /// `Dependency::any("shadow-utils").with_flags(DependencyFlags::SCRIPT_PRE)`.
+    /// Comparison flags are ignored, the constructor determines them.
+    pub fn with_flags(mut self, flags: DependencyFlags) -> Self {
+        self.sense |=
+            flags - DependencyFlags::LESS - DependencyFlags::GREATER - DependencyFlags::EQUAL;
+        self
+    }
+
+    /// A rich dependency such as `(foo >= 1.0 with foo < 2.0)`, which is validated before.
+    ///
+    /// Packages using them automatically require `rpmlib(RichDependencies)`. rpm only supports
+    /// them for requires, conflicts and weak dependencies, building a package with a rich
+    /// provides or obsoletes fails.
+    pub fn rich(dependency: RichDependency) -> Result<Self, RPMError> {
+        dependency.validate()?;
+        // a simple dependency is still valid, but not worth the rpmlib requirement
+        if let RichDependency::Simple(simple) = dependency {
+            return Ok(simple);
+        }
+        Ok(Self::new(
+            dependency.to_string(),
+            DependencyFlags::ANY,
+            "".to_string(),
+        ))
+    }
+
+    pub(crate) fn new(dep_name: String, sense: DependencyFlags, version: String) -> Self {
         Dependency {
             dep_name,
             sense,
@@ -160,3 +470,178 @@ impl Dependency {
         }
     }
 }
+
+bitflags::bitflags! {
+    /// Processing of a scriptlet body as recorded in `RPMTAG_PREINFLAGS` and alike.
+    pub struct ScriptletFlags: u32 {
+        /// expand macros in the body on installation
+        const EXPAND = RPMSCRIPT_FLAG_EXPAND;
+        /// expand query format tags in the body on installation
+        const QFORMAT = RPMSCRIPT_FLAG_QFORMAT;
+        /// abort the transaction if the scriptlet fails
+        const CRITICAL = RPMSCRIPT_FLAG_CRITICAL;
+    }
+}
+
+/// Script run by rpm on installation, removal or verification of the package.
+///
+/// Scriptlets are run by `/bin/sh` unless another interpreter is given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scriptlet {
+    pub(crate) body: String,
+    pub(crate) interpreter: String,
+    pub(crate) arguments: Vec<String>,
+    pub(crate) flags: ScriptletFlags,
+}
+
+impl Scriptlet {
+    pub fn new<T: Into<String>>(body: T) -> Self {
+        Scriptlet {
+            body: body.into(),
+            interpreter: "/bin/sh".to_string(),
+            arguments: Vec::new(),
+            flags: ScriptletFlags::empty(),
+        }
+    }
+
+    /// A scriptlet run by the lua interpreter built into rpm, which does not need to be installed.
+    pub fn lua<T: Into<String>>(body: T) -> Self {
+        Self::new(body).with_interpreter(RPMSCRIPT_LUA)
+    }
+
+    /// Absolute path of the interpreter, or `<lua>` for the built-in one.
+    ///
+    /// The interpreter is required by the package automatically.
+    pub fn with_interpreter<T: Into<String>>(mut self, interpreter: T) -> Self {
+        self.interpreter = interpreter.into();
+        self
+    }
+
+    /// Arguments passed to the interpreter in front of the script, like `-p /usr/bin/perl -w`.
+    pub fn with_arguments<I, T>(mut self, arguments: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.arguments = arguments.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_flags(mut self, flags: ScriptletFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    pub fn interpreter(&self) -> &str {
+        &self.interpreter
+    }
+
+    pub fn arguments(&self) -> &[String] {
+        &self.arguments
+    }
+
+    pub fn flags(&self) -> ScriptletFlags {
+        self.flags
+    }
+
+    pub(crate) fn is_lua(&self) -> bool {
+        self.interpreter == RPMSCRIPT_LUA
+    }
+}
+
+impl From<&str> for Scriptlet {
+    fn from(body: &str) -> Self {
+        Scriptlet::new(body)
+    }
+}
+
+impl From<String> for Scriptlet {
+    fn from(body: String) -> Self {
+        Scriptlet::new(body)
+    }
+}
+
+/// Event of another package a [`Trigger`] reacts to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriggerType {
+    /// `%triggerprein`, before the other package is installed
+    PreIn,
+    /// `%triggerin`, after the other package is installed, or this one if the other one is
+    /// already present
+    In,
+    /// `%triggerun`, before the other package is removed, or this one if the other one stays
+    Un,
+    /// `%triggerpostun`, after the other package is removed
+    PostUn,
+}
+
+impl TriggerType {
+    pub(crate) fn sense(&self) -> DependencyFlags {
+        match self {
+            TriggerType::PreIn => DependencyFlags::TRIGGERPREIN,
+            TriggerType::In => DependencyFlags::TRIGGERIN,
+            TriggerType::Un => DependencyFlags::TRIGGERUN,
+            TriggerType::PostUn => DependencyFlags::TRIGGERPOSTUN,
+        }
+    }
+
+    pub(crate) fn from_sense(sense: DependencyFlags) -> Option<Self> {
+        [
+            TriggerType::PreIn,
+            TriggerType::In,
+            TriggerType::Un,
+            TriggerType::PostUn,
+        ]
+        .iter()
+        .copied()
+        .find(|trigger_type| sense.contains(trigger_type.sense()))
+    }
+}
+
+/// Scriptlet run when another package matching one of the conditions is installed or removed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trigger {
+    pub(crate) trigger_type: TriggerType,
+    pub(crate) conditions: Vec<Dependency>,
+    pub(crate) scriptlet: Scriptlet,
+}
+
+impl Trigger {
+    /// A trigger such as `%triggerin -- httpd`.
+    ///
+    /// Rpm records a single interpreter per trigger, the scriptlet must not have any
+    /// interpreter arguments.
+    pub fn new<T: Into<Scriptlet>>(
+        trigger_type: TriggerType,
+        condition: Dependency,
+        scriptlet: T,
+    ) -> Self {
+        Trigger {
+            trigger_type,
+            conditions: vec![condition],
+            scriptlet: scriptlet.into(),
+        }
+    }
+
+    /// Fire the trigger for another package too, like `%triggerin -- httpd, nginx`.
+    pub fn with_condition(mut self, condition: Dependency) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    pub fn trigger_type(&self) -> TriggerType {
+        self.trigger_type
+    }
+
+    pub fn conditions(&self) -> &[Dependency] {
+        &self.conditions
+    }
+
+    pub fn scriptlet(&self) -> &Scriptlet {
+        &self.scriptlet
+    }
+}
@@ -1,7 +1,9 @@
 mod builder;
 mod compressor;
+mod extract;
 mod headers;
 mod package;
+mod payload;
 
 #[cfg(feature = "signature-meta")]
 pub mod signature;
@@ -10,6 +12,10 @@ pub use headers::*;
 
 pub use compressor::*;
 
+pub use extract::*;
+
 pub use package::*;
 
+pub use payload::*;
+
 pub use builder::*;
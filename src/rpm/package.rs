@@ -7,10 +7,14 @@ use crate::sequential_cursor::SeqCursor;
 
 use crate::errors::*;
 
+use super::compressor::Decompressor;
+use super::extract::{extract_files, RPMExtractOptions};
+use super::payload::{payload_decompressor, RPMFileIterator};
 use super::Lead;
 use crate::signature;
 
-use std::io::{Read, Seek, SeekFrom};
+use std::io::{BufRead, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 /// A complete rpm file.
 ///
 /// Can either be created using the [`RPMPackageBuilder`](super::builder::RPMPackageBuilder)
@@ -25,11 +29,11 @@ pub struct RPMPackage {
 }
 
 impl RPMPackage {
+    /// Parse a complete rpm file, reading the payload into memory.
+    ///
+    /// Use [`RPMPackageReader`](self::RPMPackageReader) to avoid the latter.
     pub fn parse<T: std::io::BufRead>(input: &mut T) -> Result<Self, RPMError> {
-        let metadata = RPMPackageMetadata::parse(input)?;
-        let mut content = Vec::new();
-        input.read_to_end(&mut content)?;
-        Ok(RPMPackage { metadata, content })
+        RPMPackageReader::new(input)?.into_package()
     }
 
     pub fn write<W: std::io::Write>(&self, out: &mut W) -> Result<(), RPMError> {
@@ -38,6 +42,34 @@ impl RPMPackage {
         Ok(())
     }
 
+    /// Decompressed payload, a cpio archive in `newc` format.
+    pub fn payload_reader(&self) -> Result<Decompressor<&[u8]>, RPMError> {
+        payload_decompressor(&self.metadata.header, self.content.as_slice())
+    }
+
+    /// Iterate over all files contained in the payload.
+    ///
+    /// Each file is joined with its metadata as recorded in the header.
+    pub fn files(&self) -> Result<RPMFileIterator<Decompressor<&[u8]>>, RPMError> {
+        Ok(RPMFileIterator::new(
+            self.payload_reader()?,
+            self.metadata.header.get_file_entries()?,
+        ))
+    }
+
+    /// Unpack all files, directories and symlinks into `root`.
+    ///
+    /// Equivalent to `rpm2cpio | cpio -idm`, but applies the modes, mtimes and optionally the owners
+    /// as recorded in the header. Paths escaping `root` are refused. Existing `%config` files are
+    /// left untouched and returned instead.
+    pub fn extract_to<P: AsRef<Path>>(
+        &self,
+        root: P,
+        options: RPMExtractOptions,
+    ) -> Result<Vec<PathBuf>, RPMError> {
+        extract_files(self.files()?, root.as_ref(), options)
+    }
+
     // TODO allow passing an external signer/verifier
 
     /// sign all headers (except for the lead) using an external key and store it as the initial header
@@ -80,7 +112,7 @@ impl RPMPackage {
         // TODO FIXME verify this is the size we want, I don't think it is
         // TODO maybe use signature_size instead of size
         self.metadata.signature = Header::<IndexSignatureTag>::new_signature_header(
-            header_and_content_cursor.len() as i32,
+            header_and_content_cursor.len() as u64,
             digest_md5,
             digest_sha1.to_string(),
             rsa_signature_spanning_header_only.as_slice(),
@@ -139,7 +171,10 @@ pub struct RPMPackageMetadata {
 }
 
 impl RPMPackageMetadata {
-    pub(crate) fn parse<T: std::io::BufRead>(input: &mut T) -> Result<Self, RPMError> {
+    /// Parse lead, signature and header only.
+    ///
+    /// `input` is left positioned at the start of the payload.
+    pub fn parse<T: std::io::BufRead>(input: &mut T) -> Result<Self, RPMError> {
         let mut lead_buffer = [0; LEAD_SIZE];
         input.read_exact(&mut lead_buffer)?;
         let lead = Lead::parse(&lead_buffer)?;
@@ -159,3 +194,72 @@ impl RPMPackageMetadata {
         Ok(())
     }
 }
+
+/// Reader of a rpm file which parses the headers only.
+///
+/// The underlying reader is kept positioned at the start of the payload, which can be streamed,
+/// read into memory or skipped entirely. Inspecting the metadata of large packages is thus
+/// possible without loading their payload.
+pub struct RPMPackageReader<R: BufRead> {
+    metadata: RPMPackageMetadata,
+    payload: R,
+}
+
+impl<R: BufRead> RPMPackageReader<R> {
+    /// Parse the headers from `input`.
+    pub fn new(mut input: R) -> Result<Self, RPMError> {
+        let metadata = RPMPackageMetadata::parse(&mut input)?;
+        Ok(RPMPackageReader {
+            metadata,
+            payload: input,
+        })
+    }
+
+    pub fn metadata(&self) -> &RPMPackageMetadata {
+        &self.metadata
+    }
+
+    /// Decompressed payload, a cpio archive in `newc` format.
+    pub fn payload_reader(&mut self) -> Result<Decompressor<&mut R>, RPMError> {
+        payload_decompressor(&self.metadata.header, &mut self.payload)
+    }
+
+    /// Iterate over all files contained in the payload.
+    ///
+    /// See [`RPMPackage::files`](self::RPMPackage::files).
+    pub fn files(&mut self) -> Result<RPMFileIterator<Decompressor<&mut R>>, RPMError> {
+        let entries = self.metadata.header.get_file_entries()?;
+        Ok(RPMFileIterator::new(self.payload_reader()?, entries))
+    }
+
+    /// Unpack all files, directories and symlinks into `root`.
+    ///
+    /// See [`RPMPackage::extract_to`](self::RPMPackage::extract_to).
+    pub fn extract_to<P: AsRef<Path>>(
+        &mut self,
+        root: P,
+        options: RPMExtractOptions,
+    ) -> Result<Vec<PathBuf>, RPMError> {
+        extract_files(self.files()?, root.as_ref(), options)
+    }
+
+    /// Discard the payload, keeping the metadata only.
+    pub fn into_metadata(self) -> RPMPackageMetadata {
+        self.metadata
+    }
+
+    /// The metadata and the reader of the raw, possibly compressed payload.
+    pub fn into_parts(self) -> (RPMPackageMetadata, R) {
+        (self.metadata, self.payload)
+    }
+
+    /// Read the remaining payload into memory.
+    pub fn into_package(mut self) -> Result<RPMPackage, RPMError> {
+        let mut content = Vec::new();
+        self.payload.read_to_end(&mut content)?;
+        Ok(RPMPackage {
+            metadata: self.metadata,
+            content,
+        })
+    }
+}
@@ -0,0 +1,138 @@
+//! Access to the files within the payload of a parsed rpm file.
+
+use std::collections::HashMap;
+use std::io::{BufRead, Read};
+use std::path::{Path, PathBuf};
+
+use super::compressor::Decompressor;
+use super::headers::*;
+use crate::constants::*;
+use crate::errors::*;
+
+/// A file from the payload joined with its metadata from the header.
+pub struct RPMFile {
+    pub metadata: FileEntry,
+    /// Uncompressed file content, the link target for symlinks.
+    ///
+    /// Empty for all but the last member of a hardlink set.
+    pub content: Vec<u8>,
+}
+
+/// A file from the payload whose content is read straight from the archive.
+///
+/// Reading yields the uncompressed file content, the link target for symlinks. Returned by
+/// [`RPMFileIterator::next_file`], which avoids loading entire files into memory.
+pub struct RPMFileReader<'a, R: Read> {
+    pub metadata: FileEntry,
+    pub(crate) content: &'a mut cpio::newc::Reader<R>,
+}
+
+impl<'a, R: Read> RPMFileReader<'a, R> {
+    /// Size of the content within the archive, zero for all but the last member of a hardlink set.
+    pub fn content_size(&self) -> u64 {
+        self.content.entry().file_size() as u64
+    }
+}
+
+impl<'a, R: Read> Read for RPMFileReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.content.read(buf)
+    }
+}
+
+/// Iterator over the files contained in a cpio payload.
+///
+/// Yields the files in the order of the archive. Entries without any payload data,
+/// i.e. `%ghost` files, are only present in the header and thus never yielded.
+///
+/// Iterating reads each file into memory, use [`next_file`](Self::next_file) to stream them instead.
+pub struct RPMFileIterator<R: Read> {
+    archive: Option<R>,
+    /// member of the archive whose content is currently read
+    current: Option<cpio::newc::Reader<R>>,
+    entries: HashMap<PathBuf, FileEntry>,
+}
+
+impl<R: Read> RPMFileIterator<R> {
+    pub(crate) fn new(archive: R, entries: Vec<FileEntry>) -> Self {
+        RPMFileIterator {
+            archive: Some(archive),
+            current: None,
+            entries: entries
+                .into_iter()
+                .map(|entry| (entry.path.clone(), entry))
+                .collect(),
+        }
+    }
+
+    /// Advance to the next file, whose content can be read until the following call.
+    ///
+    /// Unread content is skipped.
+    pub fn next_file(&mut self) -> Result<Option<RPMFileReader<'_, R>>, RPMError> {
+        // the archive is only put back once the previous entry was skipped successfully,
+        // so iteration ends after the first error
+        if let Some(current) = self.current.take() {
+            self.archive = Some(current.finish()?);
+        }
+        let archive = match self.archive.take() {
+            Some(archive) => archive,
+            None => return Ok(None),
+        };
+        let reader = cpio::newc::Reader::new(archive)?;
+        if reader.entry().is_trailer() {
+            return Ok(None);
+        }
+        let name = reader.entry().name().to_string();
+        let metadata = self
+            .entries
+            .remove(&header_path(&name))
+            .ok_or(RPMError::PayloadEntryNotInHeader(name))?;
+        Ok(Some(RPMFileReader {
+            metadata,
+            content: self.current.insert(reader),
+        }))
+    }
+}
+
+impl<R: Read> Iterator for RPMFileIterator<R> {
+    type Item = Result<RPMFile, RPMError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let file = match self.next_file() {
+            Ok(Some(file)) => file,
+            Ok(None) => return None,
+            Err(e) => return Some(Err(e)),
+        };
+        let mut content = Vec::new();
+        Some(match file.content.read_to_end(&mut content) {
+            Ok(_) => Ok(RPMFile {
+                metadata: file.metadata,
+                content,
+            }),
+            Err(e) => Err(e.into()),
+        })
+    }
+}
+
+/// Map a cpio member name (`./usr/bin/foo` or `usr/bin/foo`) to the absolute path used in the header.
+fn header_path(cpio_name: &str) -> PathBuf {
+    let name = cpio_name.strip_prefix('.').unwrap_or(cpio_name);
+    Path::new("/").join(name.trim_start_matches('/'))
+}
+
+/// Create the decoder for a payload as described by `header`.
+pub(crate) fn payload_decompressor<R: BufRead>(
+    header: &Header<IndexTag>,
+    mut payload: R,
+) -> Result<Decompressor<R>, RPMError> {
+    match header.get_payload_compressor() {
+        Ok(name) => Decompressor::new(name, payload),
+        // uncompressed packages built by this crate do not carry a compressor tag,
+        // while rpm itself assumes gzip in that case
+        Err(RPMError::TagNotFound(_)) => {
+            let is_gzip = payload.fill_buf()?.starts_with(&GZIP_MAGIC);
+            Decompressor::new(if is_gzip { "gzip" } else { "none" }, payload)
+        }
+        Err(e) => Err(e),
+    }
+}
@@ -12,6 +12,28 @@ fn cargo_manifest_dir() -> std::path::PathBuf {
     std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
 }
 
+/// Builder of the minimal package the tests add their specifics to.
+fn test_builder() -> RPMBuilder {
+    RPMBuilder::new("test", "1.0.0", "MIT", "x86_64", "some package")
+}
+
+/// Walk the cpio archive of `pkg`, mapping each entry except for the trailer with `f`.
+fn cpio_entries<T, F>(pkg: &RPMPackage, f: F) -> Result<Vec<T>, Box<dyn std::error::Error>>
+where
+    F: Fn(&cpio::newc::Entry) -> T,
+{
+    let mut archive = pkg.payload_reader()?;
+    let mut entries = Vec::new();
+    loop {
+        let reader = cpio::newc::Reader::new(archive)?;
+        if reader.entry().is_trailer() {
+            return Ok(entries);
+        }
+        entries.push(f(reader.entry()));
+        archive = reader.finish()?;
+    }
+}
+
 #[test]
 fn test_rpm_header() -> Result<(), Box<dyn std::error::Error>> {
     let rpm_file_path = test_rpm_file_path();
@@ -273,3 +295,1357 @@ fn test_region_tag() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn test_payload_files_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+    let cargo_file = cargo_manifest_dir().join("Cargo.toml");
+    let pkg = test_builder()
+        .compression(std::str::FromStr::from_str("gzip")?)
+        .with_file(
+            &cargo_file,
+            RPMFileOptions::new("/etc/foobar/foo.toml").is_config(),
+        )?
+        .with_file(
+            &cargo_file,
+            RPMFileOptions::new("/usr/share/foobar/bar.toml")
+                .mode(0o100_644)
+                .user("hugo"),
+        )?
+        .build()?;
+
+    let mut buf = Vec::new();
+    pkg.write(&mut buf)?;
+    let pkg = RPMPackage::parse(&mut buf.as_slice())?;
+
+    let expected_content = std::fs::read(&cargo_file)?;
+    let files = pkg.files()?.collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(2, files.len());
+
+    assert_eq!(
+        std::path::PathBuf::from("/etc/foobar/foo.toml"),
+        files[0].metadata.path
+    );
+    assert_eq!(FileFlags::CONFIG, files[0].metadata.flags);
+    assert_eq!(expected_content, files[0].content);
+
+    assert_eq!(
+        std::path::PathBuf::from("/usr/share/foobar/bar.toml"),
+        files[1].metadata.path
+    );
+    assert_eq!(0o100_644, files[1].metadata.mode);
+    assert_eq!("hugo", files[1].metadata.user);
+    assert_eq!(expected_content.len() as u64, files[1].metadata.size);
+    assert_eq!(expected_content, files[1].content);
+
+    Ok(())
+}
+
+#[test]
+fn test_extract_to() -> Result<(), Box<dyn std::error::Error>> {
+    let cargo_file = cargo_manifest_dir().join("Cargo.toml");
+    let pkg = test_builder()
+        .with_file(
+            &cargo_file,
+            RPMFileOptions::new("/etc/foobar/foo.toml").is_config(),
+        )?
+        .with_file(
+            &cargo_file,
+            RPMFileOptions::new("/usr/share/foobar/bar.toml").mode(0o100_600),
+        )?
+        .build()?;
+
+    let root = cargo_manifest_dir().join("target/extract_test");
+    let _ = std::fs::remove_dir_all(&root);
+
+    let existing_configs = pkg.extract_to(&root, RPMExtractOptions::new())?;
+    assert!(existing_configs.is_empty());
+    let expected_content = std::fs::read(&cargo_file)?;
+    assert_eq!(
+        expected_content,
+        std::fs::read(root.join("usr/share/foobar/bar.toml"))?
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(root.join("usr/share/foobar/bar.toml"))?
+            .permissions()
+            .mode();
+        assert_eq!(0o600, mode & 0o7777);
+    }
+
+    std::fs::write(root.join("etc/foobar/foo.toml"), "modified")?;
+    let existing_configs = pkg.extract_to(&root, RPMExtractOptions::new())?;
+    assert_eq!(
+        vec![std::path::PathBuf::from("/etc/foobar/foo.toml")],
+        existing_configs
+    );
+    assert_eq!(
+        "modified",
+        std::fs::read_to_string(root.join("etc/foobar/foo.toml"))?
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_extract_to_deferred_symlink_traversal() -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let entry = |path: &str, mode: u32, size: u64, link: &str, inode: u32| FileEntry {
+        path: std::path::PathBuf::from(path),
+        size,
+        mode,
+        modified_at: 0,
+        digest: String::new(),
+        link: link.to_string(),
+        flags: FileFlags::empty(),
+        verify_flags: FileVerifyFlags::all(),
+        user: "root".to_string(),
+        group: "root".to_string(),
+        inode,
+    };
+    // payload without the checks of the builder
+    let archive = |entries: Vec<(FileEntry, &[u8])>| -> io::Result<_> {
+        let mut archive = Vec::new();
+        for (entry, content) in &entries {
+            let name = format!(".{}", entry.path.display());
+            let mut writer = cpio::newc::Builder::new(&name)
+                .mode(entry.mode)
+                .ino(entry.inode)
+                .write(&mut archive, content.len() as u32);
+            writer.write_all(content)?;
+            writer.finish()?;
+        }
+        cpio::newc::trailer(&mut archive)?;
+        let entries = entries.into_iter().map(|(entry, _)| entry).collect();
+        Ok(RPMFileIterator::new(io::Cursor::new(archive), entries))
+    };
+
+    let base = cargo_manifest_dir().join("target/extract_traversal_test");
+    let _ = std::fs::remove_dir_all(&base);
+    let outside = base.join("outside");
+    std::fs::create_dir_all(outside.join("sub"))?;
+    std::fs::set_permissions(outside.join("sub"), std::fs::Permissions::from_mode(0o755))?;
+    // relative to the roots below, absolute targets are rooted at the extraction root
+    let outside_link = "../outside";
+
+    // directory metadata is applied after the directory was replaced by a symlink
+    let files = archive(vec![
+        (entry("/d/sub", S_IFDIR | 0o777, 0, "", 1), &b""[..]),
+        (entry("/d", S_IFLNK | 0o777, 0, outside_link, 2), &b""[..]),
+    ])?;
+    let result = extract_files(files, &base.join("root"), RPMExtractOptions::new());
+    assert!(matches!(
+        result,
+        Err(RPMError::InvalidDestinationPath { .. })
+    ));
+    let mode = std::fs::metadata(outside.join("sub"))?.permissions().mode();
+    assert_eq!(0o755, mode & 0o7777);
+
+    // the parent of a pending hardlink is replaced by a symlink before the content shows up
+    let files = archive(vec![
+        (entry("/h/link", S_IFREG | 0o644, 1, "", 3), &b""[..]),
+        (entry("/h", S_IFLNK | 0o777, 0, outside_link, 4), &b""[..]),
+        (entry("/h2", S_IFREG | 0o644, 1, "", 3), &b"x"[..]),
+    ])?;
+    let result = extract_files(files, &base.join("root2"), RPMExtractOptions::new());
+    assert!(matches!(
+        result,
+        Err(RPMError::InvalidDestinationPath { .. })
+    ));
+    assert!(!outside.join("link").exists());
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_extract_to_symlinked_parents() -> Result<(), Box<dyn std::error::Error>> {
+    let pkg = test_builder()
+        .with_dir(RPMFileOptions::new("/lib").mode(0o755))?
+        .with_dir(RPMFileOptions::new("/lib/modules").mode(0o700))?
+        .with_file_contents("foo", RPMFileOptions::new("/lib/modules/foo"))?
+        .with_file_contents("#!/bin/sh\n", RPMFileOptions::new("/bin/sh"))?
+        .build()?;
+
+    // merged /usr layout with a relative and an absolute symlink
+    let root = cargo_manifest_dir().join("target/extract_symlinked_parents_test");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("usr/lib"))?;
+    std::fs::create_dir_all(root.join("usr/bin"))?;
+    std::os::unix::fs::symlink("usr/lib", root.join("lib"))?;
+    std::os::unix::fs::symlink("/usr/bin", root.join("bin"))?;
+    pkg.extract_to(&root, RPMExtractOptions::new())?;
+
+    assert!(std::fs::symlink_metadata(root.join("lib"))?
+        .file_type()
+        .is_symlink());
+    assert!(std::fs::symlink_metadata(root.join("bin"))?
+        .file_type()
+        .is_symlink());
+    assert_eq!(
+        "foo",
+        std::fs::read_to_string(root.join("usr/lib/modules/foo"))?
+    );
+    assert_eq!(
+        "#!/bin/sh\n",
+        std::fs::read_to_string(root.join("usr/bin/sh"))?
+    );
+
+    // leaving the root through a symlink is still refused
+    let root = cargo_manifest_dir().join("target/extract_symlinked_parents_escape_test");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(&root)?;
+    std::os::unix::fs::symlink("../../usr/lib", root.join("lib"))?;
+    assert!(matches!(
+        pkg.extract_to(&root, RPMExtractOptions::new()),
+        Err(RPMError::InvalidDestinationPath { .. })
+    ));
+
+    Ok(())
+}
+
+/// Make sure all files of a package can be read and match their sha256 digests.
+#[cfg(any(feature = "compression-xz", feature = "compression-zstd"))]
+fn assert_payload_digests(rpm_file_path: std::path::PathBuf) -> Result<(), RPMError> {
+    use sha2::Digest;
+
+    let rpm_file = std::fs::File::open(rpm_file_path)?;
+    let package = RPMPackage::parse(&mut std::io::BufReader::new(rpm_file))?;
+    let mut count = 0;
+    for file in package.files()? {
+        let file = file?;
+        if file.metadata.mode & S_IFMT == S_IFREG && !file.content.is_empty() {
+            let digest = format!("{:x}", sha2::Sha256::digest(&file.content));
+            assert_eq!(file.metadata.digest, digest);
+        }
+        count += 1;
+    }
+    assert_eq!(package.metadata.header.get_file_names()?.len(), count);
+    Ok(())
+}
+
+#[cfg(feature = "compression-xz")]
+#[test]
+fn test_xz_payload_files() -> Result<(), Box<dyn std::error::Error>> {
+    assert_payload_digests(test_rpm_file_path())?;
+
+    let cargo_file = cargo_manifest_dir().join("Cargo.toml");
+    for (compressor, name, capability) in [
+        (Compressor::xz(2, 2)?, "xz", "rpmlib(PayloadIsXz)"),
+        (Compressor::lzma(1)?, "lzma", "rpmlib(PayloadIsLzma)"),
+    ] {
+        let pkg = test_builder()
+            .compression(compressor)
+            .with_file(&cargo_file, RPMFileOptions::new("/etc/foobar/foo.toml"))?
+            .build()?;
+        let header = &pkg.metadata.header;
+        assert_eq!(name, header.get_payload_compressor()?);
+        assert!(header
+            .get_entry_string_array_data(IndexTag::RPMTAG_REQUIRENAME)?
+            .iter()
+            .any(|requirement| requirement == capability));
+
+        let files = pkg.files()?.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(std::fs::read(&cargo_file)?, files[0].content);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "compression-zstd")]
+#[test]
+fn test_zstd_payload_files() -> Result<(), Box<dyn std::error::Error>> {
+    assert_payload_digests(
+        cargo_manifest_dir().join("test_assets/rpm-sign-4.15.1-1.fc31.x86_64.rpm"),
+    )?;
+
+    let cargo_file = cargo_manifest_dir().join("Cargo.toml");
+    let pkg = test_builder()
+        .compression(Compressor::zstd(19, 2)?)
+        .with_file(&cargo_file, RPMFileOptions::new("/etc/foobar/foo.toml"))?
+        .build()?;
+    let header = &pkg.metadata.header;
+    assert_eq!("zstd", header.get_payload_compressor()?);
+    assert_eq!(
+        "19",
+        header.get_entry_string_data(IndexTag::RPMTAG_PAYLOADFLAGS)?
+    );
+    assert!(header
+        .get_entry_string_array_data(IndexTag::RPMTAG_REQUIRENAME)?
+        .iter()
+        .any(|requirement| requirement == "rpmlib(PayloadIsZstd)"));
+
+    let files = pkg.files()?.collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(std::fs::read(&cargo_file)?, files[0].content);
+
+    Ok(())
+}
+
+#[cfg(feature = "compression-bzip2")]
+#[test]
+fn test_bzip2_payload_files() -> Result<(), Box<dyn std::error::Error>> {
+    let cargo_file = cargo_manifest_dir().join("Cargo.toml");
+    let pkg = test_builder()
+        .compression(std::str::FromStr::from_str("bzip2")?)
+        .with_file(&cargo_file, RPMFileOptions::new("/etc/foobar/foo.toml"))?
+        .build()?;
+    assert_eq!("bzip2", pkg.metadata.header.get_payload_compressor()?);
+
+    let mut buf = Vec::new();
+    pkg.write(&mut buf)?;
+    let pkg = RPMPackage::parse(&mut buf.as_slice())?;
+    let files = pkg.files()?.collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(std::fs::read(&cargo_file)?, files[0].content);
+
+    assert!(Compressor::bzip2(0).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_gzip_compression_level() -> Result<(), Box<dyn std::error::Error>> {
+    let cargo_file = cargo_manifest_dir().join("Cargo.toml");
+    let build = |compressor| -> Result<RPMPackage, RPMError> {
+        test_builder()
+            .compression(compressor)
+            .with_file(&cargo_file, RPMFileOptions::new("/etc/foobar/foo.toml"))?
+            .build()
+    };
+    let fast = build(Compressor::gzip(1)?)?;
+    let best = build(std::str::FromStr::from_str("gzip")?)?;
+    for (pkg, level) in &[(&fast, "1"), (&best, "9")] {
+        assert_eq!(
+            *level,
+            pkg.metadata
+                .header
+                .get_entry_string_data(IndexTag::RPMTAG_PAYLOADFLAGS)?
+        );
+        let files = pkg.files()?.collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(std::fs::read(&cargo_file)?, files[0].content);
+    }
+    assert!(best.content.len() <= fast.content.len());
+    assert!(Compressor::gzip(10).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_build_to() -> Result<(), Box<dyn std::error::Error>> {
+    let cargo_file = cargo_manifest_dir().join("Cargo.toml");
+    let builder = |compressor| -> Result<RPMBuilder, RPMError> {
+        test_builder()
+            .compression(compressor)
+            .with_file(&cargo_file, RPMFileOptions::new("/etc/foobar/foo.toml"))?
+            // large enough to be written in multiple chunks
+            .with_file(
+                test_rpm_file_path(),
+                RPMFileOptions::new("/usr/share/foobar/bar.rpm"),
+            )
+    };
+
+    for compressor in &["none", "gzip"] {
+        let mut expected = Vec::new();
+        builder(compressor.parse()?)?
+            .build()?
+            .write(&mut expected)?;
+
+        let out_path = cargo_manifest_dir().join("target/build_to_test.rpm");
+        let mut out = std::fs::File::create(&out_path)?;
+        let metadata = builder(compressor.parse()?)?.build_to(&mut out)?;
+        drop(out);
+        assert_eq!("test", metadata.header.get_name()?);
+        assert_eq!(expected, std::fs::read(&out_path)?);
+    }
+
+    let source = cargo_manifest_dir().join("target/build_to_source.toml");
+    std::fs::copy(&cargo_file, &source)?;
+    let builder = test_builder().with_file(&source, RPMFileOptions::new("/etc/foobar/foo.toml"))?;
+    std::fs::write(&source, "modified")?;
+    let mut out = io::Cursor::new(Vec::new());
+    assert!(matches!(
+        builder.build_to(&mut out),
+        Err(RPMError::SourceFileChanged(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_package_reader() -> Result<(), Box<dyn std::error::Error>> {
+    let rpm_file = std::fs::File::open(test_rpm_file_path())?;
+    let reader = RPMPackageReader::new(std::io::BufReader::new(rpm_file))?;
+    let (metadata, mut payload) = reader.into_parts();
+    let mut content = Vec::new();
+    payload.read_to_end(&mut content)?;
+
+    let package = RPMPackage::parse(&mut std::io::BufReader::new(std::fs::File::open(
+        test_rpm_file_path(),
+    )?))?;
+    assert!(metadata == package.metadata);
+    assert_eq!(package.content, content);
+
+    let cargo_file = cargo_manifest_dir().join("Cargo.toml");
+    let mut buf = Vec::new();
+    test_builder()
+        .compression(Compressor::gzip(6)?)
+        .with_file(&cargo_file, RPMFileOptions::new("/etc/foobar/foo.toml"))?
+        .build()?
+        .write(&mut buf)?;
+
+    let mut reader = RPMPackageReader::new(buf.as_slice())?;
+    assert_eq!("test", reader.metadata().header.get_name()?);
+    let files = reader.files()?.collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(1, files.len());
+    assert_eq!(std::fs::read(&cargo_file)?, files[0].content);
+
+    let metadata = RPMPackageMetadata::parse(&mut buf.as_slice())?;
+    assert!(metadata == reader.into_metadata());
+
+    Ok(())
+}
+
+#[test]
+fn test_file_contents() -> Result<(), Box<dyn std::error::Error>> {
+    use sha2::Digest;
+
+    let unit = "[Service]\nExecStart=/usr/bin/foobar\n";
+    let pkg = test_builder()
+        .with_file_contents(
+            unit,
+            RPMFileOptions::new("/usr/lib/systemd/system/foobar.service")
+                .mode(0o100_644)
+                .mtime(1_600_000_000),
+        )?
+        .with_file(
+            cargo_manifest_dir().join("Cargo.toml"),
+            RPMFileOptions::new("/etc/foobar/foo.toml").mtime(1_500_000_000),
+        )?
+        .build()?;
+
+    let files = pkg.files()?.collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(2, files.len());
+    assert_eq!(1_500_000_000, files[0].metadata.modified_at);
+    let service = &files[1];
+    assert_eq!(
+        std::path::PathBuf::from("/usr/lib/systemd/system/foobar.service"),
+        service.metadata.path
+    );
+    assert_eq!(unit.as_bytes(), service.content.as_slice());
+    assert_eq!(unit.len() as u64, service.metadata.size);
+    assert_eq!(0o100_644, service.metadata.mode);
+    assert_eq!(1_600_000_000, service.metadata.modified_at);
+    assert_eq!(
+        format!("{:x}", sha2::Sha256::digest(unit.as_bytes())),
+        service.metadata.digest
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_dir_entries() -> Result<(), Box<dyn std::error::Error>> {
+    let pkg = test_builder()
+        .with_dir(
+            RPMFileOptions::new("/opt/ourapp")
+                .mode(0o750)
+                .user("ourapp")
+                .group("ourapp"),
+        )?
+        .with_dir(RPMFileOptions::new("/opt/ourapp/data/").mtime(1_600_000_000))?
+        .with_file_contents("foo", RPMFileOptions::new("/opt/ourapp/data/foo"))?
+        .build()?;
+
+    let entries = pkg.metadata.header.get_file_entries()?;
+    assert_eq!(3, entries.len());
+    assert_eq!(std::path::PathBuf::from("/opt/ourapp"), entries[0].path);
+    assert_eq!(S_IFDIR | 0o750, entries[0].mode);
+    // entries which are not read from disk default to the epoch to be reproducible
+    assert_eq!(0, entries[0].modified_at);
+    assert_eq!("ourapp", entries[0].user);
+    assert_eq!("ourapp", entries[0].group);
+    assert_eq!("", entries[0].digest);
+    assert_eq!(
+        std::path::PathBuf::from("/opt/ourapp/data"),
+        entries[1].path
+    );
+    assert_eq!(S_IFDIR | 0o755, entries[1].mode);
+    assert_eq!(1_600_000_000, entries[1].modified_at);
+    assert_eq!(S_IFREG, entries[2].mode & S_IFMT);
+    assert_eq!(0, entries[2].modified_at);
+
+    let files = pkg.files()?.collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(
+        entries,
+        files.into_iter().map(|f| f.metadata).collect::<Vec<_>>()
+    );
+
+    let root = cargo_manifest_dir().join("target/dir_entries_test");
+    let _ = std::fs::remove_dir_all(&root);
+    pkg.extract_to(&root, RPMExtractOptions::new())?;
+    assert!(root.join("opt/ourapp/data").is_dir());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(root.join("opt/ourapp"))?
+            .permissions()
+            .mode();
+        assert_eq!(0o750, mode & 0o7777);
+    }
+
+    let pkg = test_builder()
+        .with_dir(RPMFileOptions::new("/opt/ourapp"))?
+        .with_dir(RPMFileOptions::new("/opt/ourapp/data").mtime(1_600_000_000))?
+        .default_mtime(1_500_000_000)
+        .build()?;
+    let entries = pkg.metadata.header.get_file_entries()?;
+    assert_eq!(1_500_000_000, entries[0].modified_at);
+    assert_eq!(1_600_000_000, entries[1].modified_at);
+
+    Ok(())
+}
+
+#[test]
+fn test_symlink_entries() -> Result<(), Box<dyn std::error::Error>> {
+    let pkg = test_builder()
+        .with_file_contents("#!/bin/sh\n", RPMFileOptions::new("/usr/lib/foobar/foo"))?
+        .with_symlink(RPMFileOptions::new("/usr/bin/foo").symlink("../lib/foobar/foo"))?
+        .build()?;
+    assert!(test_builder()
+        .with_symlink(RPMFileOptions::new("/usr/bin/foo"))
+        .is_err());
+
+    let files = pkg.files()?.collect::<Result<Vec<_>, _>>()?;
+    let link = &files[0];
+    assert_eq!(std::path::PathBuf::from("/usr/bin/foo"), link.metadata.path);
+    assert_eq!(S_IFLNK | 0o777, link.metadata.mode);
+    assert_eq!("../lib/foobar/foo", link.metadata.link);
+    assert_eq!("", link.metadata.digest);
+    assert_eq!("../lib/foobar/foo".len() as u64, link.metadata.size);
+    assert_eq!(b"../lib/foobar/foo", link.content.as_slice());
+
+    let root = cargo_manifest_dir().join("target/symlink_entries_test");
+    let _ = std::fs::remove_dir_all(&root);
+    pkg.extract_to(&root, RPMExtractOptions::new())?;
+    #[cfg(unix)]
+    {
+        assert_eq!(
+            std::path::PathBuf::from("../lib/foobar/foo"),
+            std::fs::read_link(root.join("usr/bin/foo"))?
+        );
+        assert_eq!(
+            "#!/bin/sh\n",
+            std::fs::read_to_string(root.join("usr/bin/foo"))?
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_hardlinks() -> Result<(), Box<dyn std::error::Error>> {
+    let cargo_file = cargo_manifest_dir().join("Cargo.toml");
+    let content = std::fs::read(&cargo_file)?;
+    let single = test_builder()
+        .with_file(&cargo_file, RPMFileOptions::new("/usr/bin/foo"))?
+        .build()?;
+    let pkg = test_builder()
+        .with_file(&cargo_file, RPMFileOptions::new("/usr/bin/foo"))?
+        .with_hardlink("/usr/bin/foo", RPMFileOptions::new("/usr/bin/bar"))?
+        .with_hardlink("/usr/bin/bar", RPMFileOptions::new("/usr/bin/zoo"))?
+        .with_file_contents("other", RPMFileOptions::new("/usr/bin/other"))?
+        .build()?;
+    assert!(pkg.content.len() < single.content.len() + content.len());
+    assert!(test_builder()
+        .with_hardlink("/usr/bin/foo", RPMFileOptions::new("/usr/bin/bar"))
+        .is_err());
+    // the target cannot be replaced by another entry once it is linked
+    assert!(matches!(
+        test_builder()
+            .with_file_contents("a", RPMFileOptions::new("/usr/bin/foo"))?
+            .with_hardlink("/usr/bin/foo", RPMFileOptions::new("/usr/bin/bar"))?
+            .with_dir(RPMFileOptions::new("/usr/bin/foo")),
+        Err(RPMError::InvalidDestinationPath { .. })
+    ));
+
+    let header = &pkg.metadata.header;
+    let requires_partial_sets =
+        |pkg: &RPMPackage| -> Result<bool, RPMError> {
+            Ok(pkg.metadata.header.get_requires()?.iter().any(|dep| {
+                dep.name() == "rpmlib(PartialHardlinkSets)" && dep.version() == "4.0.4-1"
+            }))
+        };
+    assert!(requires_partial_sets(&pkg)?);
+    assert!(!requires_partial_sets(&single)?);
+    assert_eq!(
+        vec![1, 1, 2, 1],
+        header.get_entry_i32_array_data(IndexTag::RPMTAG_FILEINODES)?
+    );
+    // just like rpmbuild, the link counts are left to be derived from the inodes
+    assert!(matches!(
+        header.get_entry_i32_array_data(IndexTag::RPMTAG_FILENLINKS),
+        Err(RPMError::TagNotFound(_))
+    ));
+    assert_eq!(
+        vec![vec![
+            std::path::PathBuf::from("/usr/bin/bar"),
+            std::path::PathBuf::from("/usr/bin/foo"),
+            std::path::PathBuf::from("/usr/bin/zoo"),
+        ]],
+        header.get_hardlink_sets()?
+    );
+
+    // only the last member of the set carries the content
+    let files = pkg.files()?.collect::<Result<Vec<_>, _>>()?;
+    assert!(files[0].content.is_empty());
+    assert!(files[1].content.is_empty());
+    assert_eq!(content, files[3].content);
+    for file in [&files[0], &files[1], &files[3]] {
+        assert_eq!(content.len() as u64, file.metadata.size);
+        assert_eq!(files[1].metadata.digest, file.metadata.digest);
+        assert_eq!(files[1].metadata.mode, file.metadata.mode);
+    }
+
+    // streaming the files skips the content which is not read
+    let mut streamed = pkg.files()?;
+    let mut sizes = Vec::new();
+    while let Some(mut file) = streamed.next_file()? {
+        sizes.push(file.content_size());
+        if file.metadata.path == std::path::Path::new("/usr/bin/zoo") {
+            let mut head = [0; 7];
+            file.read_exact(&mut head)?;
+            assert_eq!(&content[..7], &head);
+        }
+    }
+    assert_eq!(vec![0, 0, 5, content.len() as u64], sizes);
+
+    let root = cargo_manifest_dir().join("target/hardlinks_test");
+    let _ = std::fs::remove_dir_all(&root);
+    pkg.extract_to(&root, RPMExtractOptions::new())?;
+    assert_eq!(content, std::fs::read(root.join("usr/bin/bar"))?);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let ino = std::fs::metadata(root.join("usr/bin/foo"))?.ino();
+        assert_eq!(ino, std::fs::metadata(root.join("usr/bin/bar"))?.ino());
+        assert_eq!(ino, std::fs::metadata(root.join("usr/bin/zoo"))?.ino());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_file_flags() -> Result<(), Box<dyn std::error::Error>> {
+    let pkg = test_builder()
+        .with_file_contents(
+            "foo = 1",
+            RPMFileOptions::new("/etc/foobar/foo.conf").is_config_noreplace(),
+        )?
+        .with_file_contents(
+            "",
+            RPMFileOptions::new("/etc/foobar/local.conf")
+                .is_config_missingok()
+                .is_ghost(),
+        )?
+        .with_file_contents(
+            "MIT",
+            RPMFileOptions::new("/usr/share/licenses/foobar/LICENSE")
+                .is_doc()
+                .is_license(),
+        )?
+        .with_file_contents(
+            "read me",
+            RPMFileOptions::new("/usr/share/doc/foobar/README").is_readme(),
+        )?
+        .build()?;
+
+    let entries = pkg.metadata.header.get_file_entries()?;
+    assert_eq!(4, entries.len());
+    assert_eq!(FileFlags::CONFIG | FileFlags::NOREPLACE, entries[0].flags);
+    assert_eq!(
+        FileFlags::CONFIG | FileFlags::MISSINGOK | FileFlags::GHOST,
+        entries[1].flags
+    );
+    assert_eq!(FileFlags::README, entries[2].flags);
+    assert_eq!(FileFlags::DOC | FileFlags::LICENSE, entries[3].flags);
+
+    // ghosts are not part of the payload
+    let files = pkg.files()?.collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(3, files.len());
+    assert!(files
+        .iter()
+        .all(|file| !file.metadata.flags.contains(FileFlags::GHOST)));
+
+    Ok(())
+}
+
+#[test]
+fn test_file_verify_flags() -> Result<(), Box<dyn std::error::Error>> {
+    let not_verified = FileVerifyFlags::DIGEST | FileVerifyFlags::SIZE | FileVerifyFlags::MTIME;
+    let pkg = test_builder()
+        .with_file_contents("", RPMFileOptions::new("/etc/foobar/foo.conf"))?
+        .with_file_contents(
+            "",
+            RPMFileOptions::new("/var/lib/foobar/state")
+                .verify(FileVerifyFlags::all() - not_verified),
+        )?
+        .build()?;
+
+    assert_eq!(
+        vec![-1, !not_verified.bits() as i32],
+        pkg.metadata
+            .header
+            .get_entry_i32_array_data(IndexTag::RPMTAG_FILEVERIFYFLAGS)?
+    );
+    let entries = pkg.metadata.header.get_file_entries()?;
+    assert_eq!(FileVerifyFlags::all(), entries[0].verify_flags);
+    assert_eq!(
+        FileVerifyFlags::USER
+            | FileVerifyFlags::GROUP
+            | FileVerifyFlags::MODE
+            | FileVerifyFlags::LINKTO
+            | FileVerifyFlags::RDEV
+            | FileVerifyFlags::CAPS,
+        entries[1].verify_flags
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_special_files() -> Result<(), Box<dyn std::error::Error>> {
+    let pkg = test_builder()
+        .with_special_file(
+            SpecialFile::CharDevice { major: 5, minor: 1 },
+            RPMFileOptions::new("/dev/console").mode(0o600),
+        )?
+        .with_special_file(
+            SpecialFile::BlockDevice { major: 8, minor: 0 },
+            RPMFileOptions::new("/dev/sda").mode(0o660),
+        )?
+        .with_special_file(SpecialFile::Fifo, RPMFileOptions::new("/run/foobar.fifo"))?
+        .with_special_file(SpecialFile::Socket, RPMFileOptions::new("/run/foobar.sock"))?
+        .build()?;
+
+    let header = &pkg.metadata.header;
+    assert_eq!(
+        vec![0x0501, 0x0800, 0, 0],
+        header.get_entry_i16_array_data(IndexTag::RPMTAG_FILERDEVS)?
+    );
+    let modes = header
+        .get_file_entries()?
+        .into_iter()
+        .map(|entry| entry.mode)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            S_IFCHR | 0o600,
+            S_IFBLK | 0o660,
+            S_IFIFO | 0o644,
+            S_IFSOCK | 0o644
+        ],
+        modes
+    );
+
+    assert_eq!(
+        vec![(5, 1), (8, 0), (0, 0), (0, 0)],
+        cpio_entries(&pkg, |entry| (entry.rdev_major(), entry.rdev_minor()))?
+    );
+
+    // nvme devices and loop devices beyond 255 cannot be encoded
+    for device in [
+        SpecialFile::BlockDevice {
+            major: 259,
+            minor: 0,
+        },
+        SpecialFile::BlockDevice {
+            major: 7,
+            minor: 256,
+        },
+    ] {
+        assert!(matches!(
+            test_builder().with_special_file(device, RPMFileOptions::new("/dev/foobar")),
+            Err(RPMError::InvalidDeviceNumber { .. })
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_file_owners() -> Result<(), Box<dyn std::error::Error>> {
+    let pkg = test_builder()
+        .default_owner(10, 20)
+        .with_file_contents("a", RPMFileOptions::new("/var/lib/foobar/a"))?
+        .with_file_contents(
+            "b",
+            RPMFileOptions::new("/var/lib/foobar/b")
+                .user("foobar")
+                .uid(999)
+                .group("foobar")
+                .gid(998),
+        )?
+        .build()?;
+
+    let header = &pkg.metadata.header;
+    assert_eq!(
+        vec![10, 999],
+        header.get_entry_i32_array_data(IndexTag::RPMTAG_FILEUIDS)?
+    );
+    assert_eq!(
+        vec![20, 998],
+        header.get_entry_i32_array_data(IndexTag::RPMTAG_FILEGIDS)?
+    );
+
+    assert_eq!(
+        vec![(10, 20), (999, 998)],
+        cpio_entries(&pkg, |entry| (entry.uid(), entry.gid()))?
+    );
+
+    // the numeric ids are only recorded if given for a file
+    let pkg = test_builder()
+        .with_file_contents("a", RPMFileOptions::new("/var/lib/foobar/a"))?
+        .build()?;
+    assert!(pkg
+        .metadata
+        .header
+        .get_entry_i32_array_data(IndexTag::RPMTAG_FILEUIDS)
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_dir_tree() -> Result<(), Box<dyn std::error::Error>> {
+    let source = cargo_manifest_dir().join("target/dir_tree_source");
+    let _ = std::fs::remove_dir_all(&source);
+    for dir in &["bin", "etc", "lib", "skip"] {
+        std::fs::create_dir_all(source.join(dir))?;
+    }
+    std::fs::write(source.join("bin/app"), "#!/bin/sh\n")?;
+    std::fs::write(source.join("etc/app.conf"), "foo = 1\n")?;
+    std::fs::write(source.join("lib/libapp.so"), "elf")?;
+    std::fs::write(source.join("lib/cache.pyc"), "pyc")?;
+    std::fs::write(source.join("skip/file"), "skipped")?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(
+            source.join("bin/app"),
+            std::fs::Permissions::from_mode(0o755),
+        )?;
+        std::os::unix::fs::symlink("app", source.join("bin/app-link"))?;
+    }
+
+    let pkg = test_builder()
+        .with_dir_tree(
+            &source,
+            "/opt/app/",
+            RPMDirTreeOptions::new()
+                .exclude("**/*.pyc")
+                .exclude("skip")
+                .file_options(|path, options| {
+                    if path.starts_with("etc") {
+                        options.is_config()
+                    } else {
+                        options
+                    }
+                }),
+        )?
+        .build()?;
+
+    let entries = pkg.metadata.header.get_file_entries()?;
+    let paths = entries
+        .iter()
+        .map(|entry| entry.path.to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    let mut expected = vec![
+        "/opt/app/bin",
+        "/opt/app/bin/app",
+        "/opt/app/etc",
+        "/opt/app/etc/app.conf",
+        "/opt/app/lib",
+        "/opt/app/lib/libapp.so",
+    ];
+    #[cfg(unix)]
+    expected.insert(2, "/opt/app/bin/app-link");
+    assert_eq!(expected, paths);
+
+    let entry = |path: &str| entries.iter().find(|e| e.path.to_string_lossy() == path);
+    assert_eq!(S_IFDIR, entry("/opt/app/bin").unwrap().mode & S_IFMT);
+    assert_eq!(
+        FileFlags::CONFIG,
+        entry("/opt/app/etc/app.conf").unwrap().flags
+    );
+    assert_eq!(
+        std::fs::metadata(source.join("lib/libapp.so"))?
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs() as u32,
+        entry("/opt/app/lib/libapp.so").unwrap().modified_at
+    );
+    #[cfg(unix)]
+    {
+        assert_eq!(S_IFREG | 0o755, entry("/opt/app/bin/app").unwrap().mode);
+        let link = entry("/opt/app/bin/app-link").unwrap();
+        assert_eq!(S_IFLNK, link.mode & S_IFMT);
+        assert_eq!("app", link.link);
+    }
+
+    let only_libs = test_builder()
+        .with_dir_tree(
+            &source,
+            "/opt/app",
+            RPMDirTreeOptions::new().include("lib/*.so"),
+        )?
+        .build()?;
+    assert_eq!(
+        vec![std::path::PathBuf::from("/opt/app/lib/libapp.so")],
+        only_libs.metadata.header.get_file_names()?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_rich_dependencies() -> Result<(), Box<dyn std::error::Error>> {
+    let versioned = RichDependency::With(vec![
+        Dependency::greater_eq("foo", "1.0").into(),
+        Dependency::less("foo", "2.0").into(),
+    ]);
+    assert_eq!("(foo >= 1.0 with foo < 2.0)", versioned.to_string());
+    let conditional = RichDependency::If {
+        then: Box::new(Dependency::any("pkgA").into()),
+        condition: Box::new(Dependency::any("pkgB").into()),
+        otherwise: Some(Box::new(RichDependency::Or(vec![
+            Dependency::any("a").into(),
+            Dependency::any("perl(Foo::Bar)").into(),
+        ]))),
+    };
+    assert_eq!(
+        "(pkgA if pkgB else (a or perl(Foo::Bar)))",
+        conditional.to_string()
+    );
+
+    assert!(Dependency::rich(RichDependency::And(vec![Dependency::any("a").into()])).is_err());
+    assert!(Dependency::rich(RichDependency::Without(
+        Box::new(Dependency::any("a").into()),
+        Box::new(conditional.clone()),
+    ))
+    .is_err());
+    assert!(Dependency::rich(Dependency::any("a b").into()).is_err());
+    assert!("(a and b or c)".parse::<RichDependency>().is_err());
+    assert!("(a and b".parse::<RichDependency>().is_err());
+
+    let pkg = test_builder()
+        .requires(Dependency::rich(versioned.clone())?)
+        .requires(Dependency::any("bar"))
+        .conflicts(Dependency::rich(conditional.clone())?)
+        .build()?;
+    let header = &pkg.metadata.header;
+    assert!(header
+        .get_entry_string_array_data(IndexTag::RPMTAG_REQUIRENAME)?
+        .iter()
+        .any(|name| name == "rpmlib(RichDependencies)"));
+    assert_eq!(
+        vec![versioned],
+        header.get_rich_dependencies(IndexTag::RPMTAG_REQUIRENAME)?
+    );
+    assert_eq!(
+        vec![conditional],
+        header.get_rich_dependencies(IndexTag::RPMTAG_CONFLICTNAME)?
+    );
+    assert!(header
+        .get_rich_dependencies(IndexTag::RPMTAG_OBSOLETENAME)?
+        .is_empty());
+
+    let rich = || {
+        Dependency::rich(RichDependency::Or(vec![
+            Dependency::any("a").into(),
+            Dependency::any("b").into(),
+        ]))
+    };
+    for builder in [
+        test_builder().provides(rich()?),
+        test_builder().obsoletes(rich()?),
+    ] {
+        assert!(matches!(
+            builder.build(),
+            Err(RPMError::InvalidRichDependency(_))
+        ));
+    }
+
+    let plain = test_builder().build()?;
+    assert!(!plain
+        .metadata
+        .header
+        .get_requires()?
+        .iter()
+        .any(|dep| dep.name() == "rpmlib(RichDependencies)"));
+
+    Ok(())
+}
+
+#[test]
+fn test_weak_dependencies() -> Result<(), Box<dyn std::error::Error>> {
+    let pkg = test_builder()
+        .recommends(Dependency::greater_eq("plugin-core", "1.0"))
+        .recommends(Dependency::any("plugin-extra"))
+        .suggests(Dependency::any("plugin-docs"))
+        .supplements(Dependency::rich(RichDependency::And(vec![
+            Dependency::any("app").into(),
+            Dependency::any("langpacks-de").into(),
+        ]))?)
+        .enhances(Dependency::eq("app", "2.0"))
+        .build()?;
+
+    let mut out = Vec::new();
+    pkg.write(&mut out)?;
+    let pkg = RPMPackage::parse(&mut std::io::Cursor::new(out))?;
+    let header = &pkg.metadata.header;
+    assert_eq!(
+        vec![
+            Dependency::greater_eq("plugin-core", "1.0"),
+            Dependency::any("plugin-extra"),
+        ],
+        header.get_recommends()?
+    );
+    assert_eq!(vec![Dependency::any("plugin-docs")], header.get_suggests()?);
+    assert_eq!(
+        vec![Dependency::any("(app and langpacks-de)")],
+        header.get_supplements()?
+    );
+    assert_eq!(vec![Dependency::eq("app", "2.0")], header.get_enhances()?);
+    assert!(header
+        .get_entry_string_array_data(IndexTag::RPMTAG_REQUIRENAME)?
+        .iter()
+        .any(|name| name == "rpmlib(RichDependencies)"));
+
+    let plain = test_builder().build()?;
+    assert!(plain.metadata.header.get_recommends()?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_dependency_getters() -> Result<(), Box<dyn std::error::Error>> {
+    let rpm_file = std::fs::File::open(test_rpm_file_path())?;
+    let pkg = RPMPackage::parse(&mut std::io::BufReader::new(rpm_file))?;
+    let header = &pkg.metadata.header;
+    let provides = header.get_provides()?;
+    assert!(provides.iter().any(|dep| dep.name() == "389-ds-base-devel"
+        && dep.version() == "1.3.8.4-15.el7"
+        && dep.flags() == DependencyFlags::EQUAL));
+    let requires = header.get_requires()?;
+    let rpmlib = requires
+        .iter()
+        .find(|dep| dep.name().starts_with("rpmlib("))
+        .unwrap();
+    assert!(rpmlib.flags().contains(DependencyFlags::RPMLIB));
+    assert!(header.get_conflicts()?.is_empty());
+
+    let pkg = test_builder()
+        .requires(Dependency::greater("bar", "2.0"))
+        .conflicts(Dependency::less_eq("baz", "1.0"))
+        .obsoletes(Dependency::any("old-test"))
+        .build()?;
+    let header = &pkg.metadata.header;
+    let requires = header.get_requires()?;
+    assert_eq!(Dependency::greater("bar", "2.0"), requires[0]);
+    assert_eq!(
+        DependencyFlags::LESS | DependencyFlags::EQUAL,
+        header.get_conflicts()?[0].flags()
+    );
+    assert_eq!(vec![Dependency::any("old-test")], header.get_obsoletes()?);
+    assert!(header
+        .get_provides()?
+        .contains(&Dependency::eq("test(x86_64)", "1.0.0")));
+
+    Ok(())
+}
+
+#[test]
+fn test_dependency_flags() -> Result<(), Box<dyn std::error::Error>> {
+    let pre = Dependency::any("shadow-utils").with_flags(DependencyFlags::SCRIPT_PRE);
+    let post = Dependency::greater_eq("systemd", "239")
+        .with_flags(DependencyFlags::SCRIPT_POST | DependencyFlags::SCRIPT_POSTUN);
+    let pretrans =
+        Dependency::any("coreutils").with_flags(DependencyFlags::PRETRANS | DependencyFlags::LESS);
+    assert_eq!(DependencyFlags::SCRIPT_PRE, pre.flags());
+    assert_eq!(
+        DependencyFlags::GREATER
+            | DependencyFlags::EQUAL
+            | DependencyFlags::SCRIPT_POST
+            | DependencyFlags::SCRIPT_POSTUN,
+        post.flags()
+    );
+    // the comparison is up to the constructor
+    assert_eq!(DependencyFlags::PRETRANS, pretrans.flags());
+
+    let pkg = test_builder()
+        .requires(pre.clone())
+        .requires(post.clone())
+        .requires(pretrans.clone())
+        .requires(Dependency::any("meta-only").with_flags(DependencyFlags::META))
+        .build()?;
+    let mut out = Vec::new();
+    pkg.write(&mut out)?;
+    let pkg = RPMPackage::parse(&mut std::io::Cursor::new(out))?;
+    let requires = pkg.metadata.header.get_requires()?;
+    assert_eq!(&[pre, post, pretrans], &requires[..3]);
+    assert_eq!(DependencyFlags::META, requires[3].flags());
+    assert!(requires
+        .iter()
+        .filter(|dep| dep.name().starts_with("rpmlib("))
+        .all(|dep| dep.flags().contains(DependencyFlags::RPMLIB)));
+
+    Ok(())
+}
+
+#[test]
+fn test_rpmlib_capabilities() -> Result<(), Box<dyn std::error::Error>> {
+    let rpmlib = |pkg: &RPMPackage| -> Result<Vec<(String, String)>, RPMError> {
+        Ok(pkg
+            .metadata
+            .header
+            .get_requires()?
+            .into_iter()
+            .filter(|dep| dep.flags().contains(DependencyFlags::RPMLIB))
+            .map(|dep| (dep.name().to_string(), dep.version().to_string()))
+            .collect())
+    };
+    let capability = |name: &str, version: &str| (name.to_string(), version.to_string());
+
+    // the automatic provides of the package itself are versioned
+    let empty = test_builder().requires(Dependency::any("bar")).build()?;
+    assert_eq!(
+        vec![capability("rpmlib(VersionedDependencies)", "3.0.3-1")],
+        rpmlib(&empty)?
+    );
+
+    let pkg = test_builder()
+        .requires(Dependency::greater_eq("bar", "1.0"))
+        .with_file_contents("a", RPMFileOptions::new("/var/lib/foobar/a"))?
+        .build()?;
+    assert_eq!(
+        vec![
+            capability("rpmlib(VersionedDependencies)", "3.0.3-1"),
+            capability("rpmlib(CompressedFileNames)", "3.0.4-1"),
+            capability("rpmlib(FileDigests)", "4.6.0-1"),
+            capability("rpmlib(PayloadFilesHavePrefix)", "4.0-1"),
+        ],
+        rpmlib(&pkg)?
+    );
+    assert!(pkg
+        .metadata
+        .header
+        .get_requires()?
+        .iter()
+        .filter(|dep| dep.name().starts_with("rpmlib("))
+        .all(|dep| dep.flags()
+            == DependencyFlags::RPMLIB | DependencyFlags::LESS | DependencyFlags::EQUAL));
+
+    Ok(())
+}
+
+#[test]
+fn test_scriptlets() -> Result<(), Box<dyn std::error::Error>> {
+    let pkg = test_builder()
+        .pre_install_script("echo preinst")
+        .post_install_script(
+            Scriptlet::new("print('postinst')")
+                .with_interpreter("/usr/bin/python3")
+                .with_arguments(vec!["-s"]),
+        )
+        .pre_uninstall_script(Scriptlet::lua("print(\"preun\")"))
+        .post_uninstall_script(
+            Scriptlet::new("echo %{name}")
+                .with_flags(ScriptletFlags::EXPAND | ScriptletFlags::QFORMAT),
+        )
+        .build()?;
+    let mut out = Vec::new();
+    pkg.write(&mut out)?;
+    let pkg = RPMPackage::parse(&mut std::io::Cursor::new(out))?;
+    let header = &pkg.metadata.header;
+
+    let pre_install = header.get_pre_install_script()?.unwrap();
+    assert_eq!("echo preinst", pre_install.body());
+    assert_eq!("/bin/sh", pre_install.interpreter());
+    assert_eq!(
+        Some(
+            Scriptlet::new("print('postinst')")
+                .with_interpreter("/usr/bin/python3")
+                .with_arguments(vec!["-s"])
+        ),
+        header.get_post_install_script()?
+    );
+    assert_eq!(
+        Some(Scriptlet::lua("print(\"preun\")")),
+        header.get_pre_uninstall_script()?
+    );
+    assert_eq!(
+        ScriptletFlags::EXPAND | ScriptletFlags::QFORMAT,
+        header.get_post_uninstall_script()?.unwrap().flags()
+    );
+
+    let ldconfig = test_builder()
+        .post_install_script(Scriptlet::new("").with_interpreter("/sbin/ldconfig"))
+        .build()?;
+    let post_install = ldconfig.metadata.header.get_post_install_script()?.unwrap();
+    assert_eq!("", post_install.body());
+    assert_eq!("/sbin/ldconfig", post_install.interpreter());
+
+    let requires = header.get_requires()?;
+    let interpreters = requires
+        .iter()
+        .filter(|dep| dep.flags().contains(DependencyFlags::INTERP))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            &Dependency::any("/bin/sh")
+                .with_flags(DependencyFlags::INTERP | DependencyFlags::SCRIPT_PRE),
+            &Dependency::any("/usr/bin/python3")
+                .with_flags(DependencyFlags::INTERP | DependencyFlags::SCRIPT_POST),
+            &Dependency::any("/bin/sh")
+                .with_flags(DependencyFlags::INTERP | DependencyFlags::SCRIPT_POSTUN),
+        ],
+        interpreters
+    );
+    for capability in [
+        "rpmlib(ScriptletInterpreterArgs)",
+        "rpmlib(BuiltinLuaScripts)",
+        "rpmlib(ScriptletExpansion)",
+    ] {
+        assert!(requires.iter().any(|dep| dep.name() == capability));
+    }
+
+    let without_scriptlets = test_builder().build()?;
+    let header = &without_scriptlets.metadata.header;
+    assert!(header
+        .get_requires()?
+        .iter()
+        .all(|dep| dep.flags().contains(DependencyFlags::RPMLIB)));
+    assert_eq!(None, header.get_pre_install_script()?);
+
+    assert!(matches!(
+        test_builder()
+            .pre_install_script(Scriptlet::new("true").with_interpreter("sh"))
+            .build(),
+        Err(RPMError::InvalidScriptletInterpreter(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_transaction_and_verify_scriptlets() -> Result<(), Box<dyn std::error::Error>> {
+    let pkg = test_builder()
+        .pre_transaction_script(Scriptlet::lua("print(\"pretrans\")"))
+        .post_transaction_script("/usr/bin/update-foobar-cache")
+        .verify_script(Scriptlet::new("test -d /var/lib/foobar").with_interpreter("/bin/bash"))
+        .build()?;
+    let mut out = Vec::new();
+    pkg.write(&mut out)?;
+    let pkg = RPMPackage::parse(&mut std::io::Cursor::new(out))?;
+    let header = &pkg.metadata.header;
+
+    assert_eq!(
+        Some(Scriptlet::lua("print(\"pretrans\")")),
+        header.get_pre_transaction_script()?
+    );
+    assert_eq!(
+        Some(Scriptlet::new("/usr/bin/update-foobar-cache")),
+        header.get_post_transaction_script()?
+    );
+    assert_eq!(
+        Some(Scriptlet::new("test -d /var/lib/foobar").with_interpreter("/bin/bash")),
+        header.get_verify_script()?
+    );
+    assert_eq!(None, header.get_pre_install_script()?);
+
+    let interpreters = header
+        .get_requires()?
+        .into_iter()
+        .filter(|dep| dep.flags().contains(DependencyFlags::INTERP))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            Dependency::any("/bin/sh")
+                .with_flags(DependencyFlags::INTERP | DependencyFlags::POSTTRANS),
+            Dependency::any("/bin/bash")
+                .with_flags(DependencyFlags::INTERP | DependencyFlags::SCRIPT_VERIFY),
+        ],
+        interpreters
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_triggers() -> Result<(), Box<dyn std::error::Error>> {
+    let restart = Trigger::new(
+        TriggerType::In,
+        Dependency::greater_eq("httpd", "2.4"),
+        "systemctl try-restart foobar.service",
+    )
+    .with_condition(Dependency::any("nginx"));
+    let cleanup = Trigger::new(
+        TriggerType::PostUn,
+        Dependency::any("httpd"),
+        Scriptlet::lua("os.remove(\"/etc/foobar/httpd.conf\")"),
+    );
+    let pkg = test_builder()
+        .trigger(restart.clone())
+        .trigger(cleanup.clone())
+        .build()?;
+    let mut out = Vec::new();
+    pkg.write(&mut out)?;
+    let pkg = RPMPackage::parse(&mut std::io::Cursor::new(out))?;
+    let header = &pkg.metadata.header;
+
+    let triggers = header.get_triggers()?;
+    assert_eq!(TriggerType::In, triggers[0].trigger_type());
+    assert_eq!(2, triggers[0].conditions().len());
+    assert_eq!("/bin/sh", triggers[0].scriptlet().interpreter());
+    assert_eq!(vec![restart, cleanup], triggers);
+    assert_eq!(
+        vec![
+            (DependencyFlags::TRIGGERIN | DependencyFlags::GREATER | DependencyFlags::EQUAL).bits()
+                as i32,
+            DependencyFlags::TRIGGERIN.bits() as i32,
+            DependencyFlags::TRIGGERPOSTUN.bits() as i32,
+        ],
+        header.get_entry_i32_array_data(IndexTag::RPMTAG_TRIGGERFLAGS)?
+    );
+    assert_eq!(
+        vec![0, 0, 1],
+        header.get_entry_i32_array_data(IndexTag::RPMTAG_TRIGGERINDEX)?
+    );
+    let requires = header.get_requires()?;
+    assert!(requires.contains(&Dependency::any("/bin/sh").with_flags(DependencyFlags::INTERP)));
+    assert!(requires
+        .iter()
+        .any(|dep| dep.name() == "rpmlib(BuiltinLuaScripts)"));
+
+    assert!(test_builder()
+        .build()?
+        .metadata
+        .header
+        .get_triggers()?
+        .is_empty());
+    assert!(matches!(
+        test_builder()
+            .trigger(Trigger::new(
+                TriggerType::Un,
+                Dependency::any("httpd"),
+                Scriptlet::new("print('un')")
+                    .with_interpreter("/usr/bin/python3")
+                    .with_arguments(vec!["-s"]),
+            ))
+            .build(),
+        Err(RPMError::InvalidTrigger(_))
+    ));
+
+    Ok(())
+}